@@ -1,6 +1,10 @@
 use std::cmp::{Ordering, PartialOrd};
+use std::fmt::{self, Write};
+
+use crate::bigint::BigInt;
+use crate::lexer::token::{Span, Token};
+use crate::stack;
 
-use crate::lexer::token::Token;
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Operator {
     Sentinel,
@@ -13,9 +17,75 @@ pub enum Operator {
     Exponential,
 
     Assign,
+
+    Range,
+    RangeInclusive,
+
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+
+    And,
+    Or,
+    Not,
+}
+
+impl Operator {
+    // higher values bind tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Sentinel => 0,
+            Operator::Assign => 1,
+            Operator::Range | Operator::RangeInclusive => 2,
+            Operator::Or => 3,
+            Operator::And => 4,
+            Operator::Equal | Operator::NotEqual => 5,
+            Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual => 6,
+            Operator::Plus | Operator::Minus => 7,
+            Operator::Multiplication | Operator::Division => 8,
+            Operator::Negative | Operator::Not => 9,
+            Operator::Exponential => 10,
+        }
+    }
+
+    /// `a = b = 1` is `a = (b = 1)` and `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`,
+    /// every other binary operator groups from the left
+    pub fn is_right_associative(&self) -> bool {
+        matches!(self, Operator::Assign | Operator::Exponential)
+    }
 }
 
-pub struct NotAnOperatorError(Token);
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Operator::Sentinel => "sentinel",
+            Operator::Plus => "+",
+            Operator::Minus | Operator::Negative => "-",
+            Operator::Multiplication => "*",
+            Operator::Division => "/",
+            Operator::Exponential => "^",
+            Operator::Assign => "=",
+            Operator::Range => "..",
+            Operator::RangeInclusive => "..=",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Not => "!",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+pub struct NotAnOperatorError(pub Token);
 impl TryFrom<&Token> for Operator {
     type Error = NotAnOperatorError;
     fn try_from(value: &Token) -> Result<Self, Self::Error> {
@@ -26,6 +96,16 @@ impl TryFrom<&Token> for Operator {
             Token::Star => Ok(Operator::Multiplication),
             Token::Slash => Ok(Operator::Division),
             Token::Caret => Ok(Operator::Exponential),
+            Token::DotDot => Ok(Operator::Range),
+            Token::DotDotEq => Ok(Operator::RangeInclusive),
+            Token::Equal => Ok(Operator::Equal),
+            Token::NotEqual => Ok(Operator::NotEqual),
+            Token::Less => Ok(Operator::Less),
+            Token::LessEqual => Ok(Operator::LessEqual),
+            Token::Greater => Ok(Operator::Greater),
+            Token::GreaterEqual => Ok(Operator::GreaterEqual),
+            Token::And => Ok(Operator::And),
+            Token::Or => Ok(Operator::Or),
             _ => Err(NotAnOperatorError(value.clone())),
         }
     }
@@ -33,42 +113,223 @@ impl TryFrom<&Token> for Operator {
 
 impl PartialOrd for Operator {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let ordering = match self {
-            Operator::Assign => match other {
-                Operator::Sentinel => Ordering::Greater,
-                Operator::Assign => Ordering::Equal,
-                _ => Ordering::Less,
-            },
-            Operator::Plus | Operator::Minus => match other {
-                Operator::Sentinel => Ordering::Greater,
-                Operator::Multiplication
-                | Operator::Division
-                | Operator::Negative
-                | Operator::Exponential => Ordering::Less,
-                _ => Ordering::Equal,
-            },
-            Operator::Multiplication | Operator::Division => match other {
-                Operator::Sentinel | Operator::Minus | Operator::Plus => Ordering::Greater,
-                Operator::Negative | Operator::Exponential => Ordering::Less,
-                _ => Ordering::Equal,
-            },
-            Operator::Negative => match other {
-                Operator::Exponential => Ordering::Less,
-                _ => Ordering::Greater,
-            },
-            Operator::Exponential => Ordering::Greater,
-            Operator::Sentinel => Ordering::Less,
+        Some(self.precedence().cmp(&other.precedence()))
+    }
+}
+
+pub struct ASTNode {
+    pub kind: ASTNodeKind,
+    pub span: Span,
+}
+
+// chains like `1 + 1 + ... + 1` are as deep as they are long, so even
+// cloning, comparing or dropping the tree grows the stack when needed
+impl Clone for ASTNode {
+    fn clone(&self) -> ASTNode {
+        stack::grow(|| ASTNode::new(self.kind.clone(), self.span))
+    }
+}
+
+impl PartialEq for ASTNode {
+    fn eq(&self, other: &ASTNode) -> bool {
+        self.span == other.span && stack::grow(|| self.kind == other.kind)
+    }
+}
+
+impl fmt::Debug for ASTNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        stack::grow(|| {
+            f.debug_struct("ASTNode")
+                .field("kind", &self.kind)
+                .field("span", &self.span)
+                .finish()
+        })
+    }
+}
+
+impl Drop for ASTNode {
+    fn drop(&mut self) {
+        let kind = std::mem::replace(&mut self.kind, ASTNodeKind::Bool(false));
+        stack::grow(|| drop(kind));
+    }
+}
+
+impl ASTNode {
+    pub fn new(kind: ASTNodeKind, span: Span) -> ASTNode {
+        ASTNode { kind, span }
+    }
+
+    /// takes the kind of the node, which can't be moved out of it
+    /// directly because `ASTNode` implements `Drop`
+    pub fn into_kind(mut self) -> ASTNodeKind {
+        std::mem::replace(&mut self.kind, ASTNodeKind::Bool(false))
+    }
+
+    /// renders the node with one line per node, children indented below their parent:
+    ///
+    /// ```text
+    /// BinaryExpr +
+    ///   I32 1
+    ///   Ident a
+    /// ```
+    pub fn to_tree(&self) -> String {
+        let mut output = String::new();
+        self.write_tree(&mut output, 0);
+        output.trim_end().to_string()
+    }
+
+    /// writes the node back as code that parses to the same tree, every
+    /// operation is wrapped in parentheses so precedence is never an issue
+    pub fn to_source(&self) -> String {
+        let mut output = String::new();
+        self.write_source(&mut output);
+        output
+    }
+
+    fn write_source(&self, output: &mut String) {
+        // writing to a String never fails
+        let _ = match &self.kind {
+            ASTNodeKind::Ident(name) => write!(output, "{}", name),
+            ASTNodeKind::I32(value) => write!(output, "{}", value),
+            ASTNodeKind::BigInt(value) => write!(output, "{}", value),
+            // unlike `Debug`, `Display` never uses the exponent notation the lexer can't read
+            ASTNodeKind::F64(value) if value.fract() == 0.0 => write!(output, "{}.0", value),
+            ASTNodeKind::F64(value) => write!(output, "{}", value),
+            ASTNodeKind::Bool(value) => write!(output, "{}", value),
+            ASTNodeKind::UnaryExpr { op, inner } => {
+                let _ = write!(output, "({}", op);
+                inner.write_source(output);
+                write!(output, ")")
+            }
+            ASTNodeKind::BinaryExpr { op, lhs, rhs } => {
+                let _ = write!(output, "(");
+                stack::grow(|| lhs.write_source(output));
+                let _ = write!(output, " {} ", op);
+                stack::grow(|| rhs.write_source(output));
+                write!(output, ")")
+            }
+            ASTNodeKind::If { .. } => {
+                let _ = write!(output, "(");
+                self.write_if_source(output);
+                write!(output, ")")
+            }
+            ASTNodeKind::Block(statements) if statements.is_empty() => write!(output, "{{}}"),
+            ASTNodeKind::Block(statements) => {
+                let _ = write!(output, "{{ ");
+                for (idx, statement) in statements.iter().enumerate() {
+                    if idx > 0 {
+                        let _ = write!(output, "; ");
+                    }
+                    statement.write_source(output);
+                }
+                write!(output, " }}")
+            }
+            ASTNodeKind::Call { callee, arguments } => {
+                callee.write_source(output);
+                let _ = write!(output, "(");
+                for (idx, argument) in arguments.iter().enumerate() {
+                    if idx > 0 {
+                        let _ = write!(output, ", ");
+                    }
+                    argument.write_source(output);
+                }
+                write!(output, ")")
+            }
+            ASTNodeKind::Closure { parameters, body } => {
+                let _ = write!(output, "(|{}| ", parameters.join(", "));
+                body.write_source(output);
+                write!(output, ")")
+            }
         };
+    }
 
-        Some(ordering)
+    // `else if` chains are written without the parentheses of nested `if`s
+    fn write_if_source(&self, output: &mut String) {
+        let ASTNodeKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } = &self.kind
+        else {
+            return self.write_source(output);
+        };
+
+        output.push_str("if ");
+        condition.write_source(output);
+        output.push(' ');
+        then_branch.write_source(output);
+
+        if let Some(else_branch) = else_branch {
+            output.push_str(" else ");
+            else_branch.write_if_source(output);
+        }
+    }
+
+    fn write_tree(&self, output: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+
+        // writing to a String never fails
+        let _ = match &self.kind {
+            ASTNodeKind::Ident(name) => writeln!(output, "{}Ident {}", indent, name),
+            ASTNodeKind::I32(value) => writeln!(output, "{}I32 {}", indent, value),
+            ASTNodeKind::BigInt(value) => writeln!(output, "{}BigInt {}", indent, value),
+            ASTNodeKind::F64(value) => writeln!(output, "{}F64 {:?}", indent, value),
+            ASTNodeKind::Bool(value) => writeln!(output, "{}Bool {}", indent, value),
+            ASTNodeKind::UnaryExpr { op, .. } => writeln!(output, "{}UnaryExpr {}", indent, op),
+            ASTNodeKind::BinaryExpr { op, .. } => writeln!(output, "{}BinaryExpr {}", indent, op),
+            ASTNodeKind::If { .. } => writeln!(output, "{}If", indent),
+            ASTNodeKind::Block(_) => writeln!(output, "{}Block", indent),
+            ASTNodeKind::Call { .. } => writeln!(output, "{}Call", indent),
+            ASTNodeKind::Closure { parameters, .. } => {
+                writeln!(output, "{}Closure |{}|", indent, parameters.join(", "))
+            }
+        };
+
+        match &self.kind {
+            ASTNodeKind::Ident(_)
+            | ASTNodeKind::I32(_)
+            | ASTNodeKind::BigInt(_)
+            | ASTNodeKind::F64(_)
+            | ASTNodeKind::Bool(_) => {}
+            ASTNodeKind::UnaryExpr { inner, .. } => inner.write_tree(output, depth + 1),
+            ASTNodeKind::BinaryExpr { lhs, rhs, .. } => {
+                stack::grow(|| lhs.write_tree(output, depth + 1));
+                stack::grow(|| rhs.write_tree(output, depth + 1));
+            }
+            ASTNodeKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                condition.write_tree(output, depth + 1);
+                then_branch.write_tree(output, depth + 1);
+                if let Some(else_branch) = else_branch {
+                    else_branch.write_tree(output, depth + 1);
+                }
+            }
+            ASTNodeKind::Block(statements) => {
+                for statement in statements {
+                    statement.write_tree(output, depth + 1);
+                }
+            }
+            ASTNodeKind::Call { callee, arguments } => {
+                callee.write_tree(output, depth + 1);
+                for argument in arguments {
+                    argument.write_tree(output, depth + 1);
+                }
+            }
+            ASTNodeKind::Closure { body, .. } => body.write_tree(output, depth + 1),
+        }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum ASTNode {
+pub enum ASTNodeKind {
     Ident(String),
     I32(i32),
-    F32(f32),
+    BigInt(BigInt),
+    F64(f64),
+    Bool(bool),
     UnaryExpr {
         op: Operator,
         inner: Box<ASTNode>,
@@ -78,4 +339,274 @@ pub enum ASTNode {
         lhs: Box<ASTNode>,
         rhs: Box<ASTNode>,
     },
+    If {
+        condition: Box<ASTNode>,
+        then_branch: Box<ASTNode>,
+        else_branch: Option<Box<ASTNode>>,
+    },
+    /// `{ a; b }` evaluates to the value of its last statement
+    Block(Vec<Statement>),
+    Call {
+        callee: Box<ASTNode>,
+        arguments: Vec<ASTNode>,
+    },
+    /// `|a, b| body`, the body takes everything up to the end of the expression
+    Closure {
+        parameters: Vec<String>,
+        body: Box<ASTNode>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Statement {
+        Statement { kind, span }
+    }
+
+    /// like `ASTNode::to_tree`, expression statements are rendered as their expression
+    pub fn to_tree(&self) -> String {
+        let mut output = String::new();
+        self.write_tree(&mut output, 0);
+        output.trim_end().to_string()
+    }
+
+    /// like `ASTNode::to_source`, the statement written back as code
+    pub fn to_source(&self) -> String {
+        let mut output = String::new();
+        self.write_source(&mut output);
+        output
+    }
+
+    fn write_source(&self, output: &mut String) {
+        match &self.kind {
+            StatementKind::Expression(node) => node.write_source(output),
+            StatementKind::Let {
+                name,
+                value,
+                constant,
+            } => {
+                let keyword = if *constant { "const" } else { "let" };
+                let _ = write!(output, "{} {} = ", keyword, name);
+                value.write_source(output);
+            }
+            StatementKind::While { condition, body } => {
+                output.push_str("while ");
+                condition.write_source(output);
+                output.push(' ');
+                body.write_source(output);
+            }
+            StatementKind::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                let _ = write!(output, "for {} in ", variable);
+                iterable.write_source(output);
+                output.push(' ');
+                body.write_source(output);
+            }
+            StatementKind::Function {
+                name,
+                parameters,
+                body,
+            } => {
+                let _ = write!(output, "fn {}({}) ", name, parameters.join(", "));
+                body.write_source(output);
+            }
+            StatementKind::Return(value) => {
+                output.push_str("return");
+                if let Some(value) = value {
+                    output.push(' ');
+                    value.write_source(output);
+                }
+            }
+        }
+    }
+
+    fn write_tree(&self, output: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+
+        match &self.kind {
+            StatementKind::Expression(node) => node.write_tree(output, depth),
+            StatementKind::Let {
+                name,
+                value,
+                constant,
+            } => {
+                let keyword = if *constant { "Const" } else { "Let" };
+                let _ = writeln!(output, "{}{} {}", indent, keyword, name);
+                value.write_tree(output, depth + 1);
+            }
+            StatementKind::While { condition, body } => {
+                let _ = writeln!(output, "{}While", indent);
+                condition.write_tree(output, depth + 1);
+                body.write_tree(output, depth + 1);
+            }
+            StatementKind::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                let _ = writeln!(output, "{}For {}", indent, variable);
+                iterable.write_tree(output, depth + 1);
+                body.write_tree(output, depth + 1);
+            }
+            StatementKind::Function {
+                name,
+                parameters,
+                body,
+            } => {
+                let _ = writeln!(
+                    output,
+                    "{}Function {}({})",
+                    indent,
+                    name,
+                    parameters.join(", ")
+                );
+                body.write_tree(output, depth + 1);
+            }
+            StatementKind::Return(value) => {
+                let _ = writeln!(output, "{}Return", indent);
+                if let Some(value) = value {
+                    value.write_tree(output, depth + 1);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementKind {
+    Expression(ASTNode),
+    /// `let name = value` declares `name` in the current scope,
+    /// hiding any variable with the same name from outer scopes,
+    /// `const name = value` does the same but `name` can't be assigned to
+    Let {
+        name: String,
+        value: ASTNode,
+        constant: bool,
+    },
+    While {
+        condition: ASTNode,
+        body: ASTNode,
+    },
+    For {
+        variable: String,
+        iterable: ASTNode,
+        body: ASTNode,
+    },
+    Function {
+        name: String,
+        parameters: Vec<String>,
+        body: ASTNode,
+    },
+    Return(Option<ASTNode>),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::extract_token_stream;
+    use crate::parser::parse;
+
+    fn tree(source: &str) -> String {
+        let tokens = extract_token_stream(source.to_string()).unwrap();
+        let statements = parse(tokens).unwrap();
+        statements
+            .iter()
+            .map(|statement| statement.to_tree())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn source(source: &str) -> String {
+        let tokens = extract_token_stream(source.to_string()).unwrap();
+        let statements = parse(tokens).unwrap();
+        statements
+            .iter()
+            .map(|statement| statement.to_source())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    #[test]
+    fn test_to_source() {
+        let programs = [
+            ("-1 + 2 * 3 ^ 2", "((-1) + (2 * (3 ^ 2)))"),
+            ("a = b = 1.5 / 3.0", "(a = (b = (1.5 / 3.0)))"),
+            ("let a = 1.0", "let a = 1.0"),
+            (
+                "const big = 12345678901234567890",
+                "const big = 12345678901234567890",
+            ),
+            ("!(1 < 2) && true", "((!(1 < 2)) && true)"),
+            (
+                "if a { 1 } else if b { 2 } else { }",
+                "(if a { 1 } else if b { 2 } else {})",
+            ),
+            (
+                "fn f(x, y) { if x > y { return x }; y }",
+                "fn f(x, y) { (if (x > y) { return x }); y }",
+            ),
+            (
+                "for i in 0..=n { total = total + i }",
+                "for i in (0 ..= n) { (total = (total + i)) }",
+            ),
+            (
+                "while i < 3 { i = i + 1 }",
+                "while (i < 3) { (i = (i + 1)) }",
+            ),
+            ("apply(|x| x + 1, 2)(3)", "apply((|x| (x + 1)), 2)(3)"),
+            ("let f = || 1", "let f = (|| 1)"),
+            (
+                "2.0 * 100000000000000000000.0",
+                "(2.0 * 100000000000000000000.0)",
+            ),
+        ];
+
+        for (program, expected) in programs {
+            let written = source(program);
+            assert_eq!(written, expected, "{}", program);
+            // the code written parses back to the same tree
+            assert_eq!(source(&written), written, "{}", program);
+        }
+    }
+
+    #[test]
+    fn test_to_tree() {
+        let expected = [
+            "BinaryExpr +",
+            "  I32 1",
+            "  BinaryExpr *",
+            "    Ident a",
+            "    F64 2.5",
+        ];
+        assert_eq!(tree("1 + a * 2.5"), expected.join("\n"));
+
+        let expected = [
+            "Function twice(f, x)",
+            "  Block",
+            "    Call",
+            "      Ident f",
+            "      Call",
+            "        Ident f",
+            "        Ident x",
+            "Const b",
+            "  If",
+            "    UnaryExpr !",
+            "      Bool true",
+            "    Block",
+            "    Block",
+            "      Closure |y|",
+            "        Ident y",
+        ];
+        assert_eq!(
+            tree("fn twice(f, x) { f(f(x)) }; const b = if !true {} else { |y| y }"),
+            expected.join("\n")
+        );
+    }
 }
@@ -0,0 +1,564 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+// every limb holds 9 decimal digits, so printing and parsing
+// the number is just a matter of splitting the digits
+const BASE: u64 = 1_000_000_000;
+const DIGITS_PER_LIMB: usize = 9;
+
+/// Arbitrary precision integer stored as sign and magnitude, the
+/// magnitude is kept in little endian limbs without trailing zeros
+/// so that zero is always an empty, non negative number
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseBigIntError;
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt {
+            negative: false,
+            limbs: vec![],
+        }
+    }
+
+    fn from_parts(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        trim(&mut limbs);
+
+        BigInt {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::from_parts(false, self.limbs.clone())
+    }
+
+    /// amount of decimal digits, zero has a single digit
+    pub fn digits(&self) -> usize {
+        match self.limbs.last() {
+            Some(most_significant) => {
+                (self.limbs.len() - 1) * DIGITS_PER_LIMB + most_significant.to_string().len()
+            }
+            None => 1,
+        }
+    }
+
+    pub fn to_i32(&self) -> Option<i32> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0i64, |acc, limb| acc * BASE as i64 + *limb as i64);
+
+        let value = if self.negative { -magnitude } else { magnitude };
+        i32::try_from(value).ok()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // rust float parsing is correctly rounded, so let it do the work
+        self.to_string().parse::<f64>().unwrap_or(f64::NAN)
+    }
+
+    /// truncated division, `None` when dividing by zero
+    pub fn checked_div(&self, other: &BigInt) -> Option<BigInt> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let (quotient, _) = divmod_magnitude(&self.limbs, &other.limbs);
+        Some(BigInt::from_parts(
+            self.negative != other.negative,
+            quotient,
+        ))
+    }
+
+    /// remainder of the truncated division, it has the sign of `self`
+    pub fn checked_rem(&self, other: &BigInt) -> Option<BigInt> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let (_, remainder) = divmod_magnitude(&self.limbs, &other.limbs);
+        Some(BigInt::from_parts(self.negative, remainder))
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> BigInt {
+        let mut result = BigInt::from(1);
+        let mut base = self.clone();
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+
+        result
+    }
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+fn compare_magnitude(lhs: &[u32], rhs: &[u32]) -> Ordering {
+    lhs.len()
+        .cmp(&rhs.len())
+        .then_with(|| lhs.iter().rev().cmp(rhs.iter().rev()))
+}
+
+fn add_magnitude(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(lhs.len().max(rhs.len()) + 1);
+    let mut carry = 0u64;
+
+    for idx in 0..lhs.len().max(rhs.len()) {
+        let sum = carry + *lhs.get(idx).unwrap_or(&0) as u64 + *rhs.get(idx).unwrap_or(&0) as u64;
+        result.push((sum % BASE) as u32);
+        carry = sum / BASE;
+    }
+
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+
+    result
+}
+
+// expects lhs to be greater or equal than rhs
+fn sub_magnitude(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(lhs.len());
+    let mut borrow = 0i64;
+
+    for (idx, limb) in lhs.iter().enumerate() {
+        let mut difference = *limb as i64 - borrow - *rhs.get(idx).unwrap_or(&0) as i64;
+        borrow = 0;
+        if difference < 0 {
+            difference += BASE as i64;
+            borrow = 1;
+        }
+
+        result.push(difference as u32);
+    }
+
+    result
+}
+
+fn mul_magnitude(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+    if lhs.is_empty() || rhs.is_empty() {
+        return vec![];
+    }
+
+    let mut result = vec![0u64; lhs.len() + rhs.len()];
+    for (i, left) in lhs.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, right) in rhs.iter().enumerate() {
+            let current = result[i + j] + *left as u64 * *right as u64 + carry;
+            result[i + j] = current % BASE;
+            carry = current / BASE;
+        }
+
+        result[i + rhs.len()] += carry;
+    }
+
+    result.into_iter().map(|limb| limb as u32).collect()
+}
+
+fn mul_small(lhs: &[u32], rhs: u64) -> Vec<u32> {
+    let mut product = mul_magnitude(lhs, &[rhs as u32]);
+    trim(&mut product);
+    product
+}
+
+// short division by a single limb, returns the quotient and the remainder
+fn divmod_small(lhs: &[u32], rhs: u64) -> (Vec<u32>, u64) {
+    let mut quotient = vec![0u32; lhs.len()];
+    let mut remainder = 0u64;
+
+    for idx in (0..lhs.len()).rev() {
+        let current = remainder * BASE + lhs[idx] as u64;
+        quotient[idx] = (current / rhs) as u32;
+        remainder = current % rhs;
+    }
+
+    trim(&mut quotient);
+    (quotient, remainder)
+}
+
+// long division (knuth's algorithm d), every limb of the quotient is
+// estimated from the leading limbs and corrected at most twice
+fn divmod_magnitude(lhs: &[u32], rhs: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if compare_magnitude(lhs, rhs) == Ordering::Less {
+        return (vec![], lhs.to_vec());
+    }
+
+    if rhs.len() == 1 {
+        let (quotient, remainder) = divmod_small(lhs, rhs[0] as u64);
+        let mut remainder = vec![remainder as u32];
+        trim(&mut remainder);
+        return (quotient, remainder);
+    }
+
+    // scale both sides so that the leading limb of the divisor is at least
+    // BASE / 2, which keeps every estimate within two of the real digit
+    let scale = BASE / (*rhs.last().unwrap() as u64 + 1);
+    let divisor = mul_small(rhs, scale);
+    // one more limb than lhs, the leading one may be zero
+    let mut remainder = mul_magnitude(lhs, &[scale as u32]);
+
+    let size = divisor.len();
+    let leading = divisor[size - 1] as u64;
+    let second = divisor[size - 2] as u64;
+    let mut quotient = vec![0u32; remainder.len() - size];
+
+    for idx in (0..quotient.len()).rev() {
+        let top = remainder[idx + size] as u64 * BASE + remainder[idx + size - 1] as u64;
+        let mut estimate = top / leading;
+        let mut rest = top % leading;
+
+        while estimate >= BASE || estimate * second > rest * BASE + remainder[idx + size - 2] as u64
+        {
+            estimate -= 1;
+            rest += leading;
+            if rest >= BASE {
+                break;
+            }
+        }
+
+        // remainder -= divisor * estimate, shifted by idx limbs
+        let mut carry = 0u64;
+        let mut borrow = 0i64;
+        for (offset, limb) in divisor.iter().enumerate() {
+            let product = estimate * *limb as u64 + carry;
+            carry = product / BASE;
+
+            let mut difference = remainder[idx + offset] as i64 - borrow - (product % BASE) as i64;
+            borrow = 0;
+            if difference < 0 {
+                difference += BASE as i64;
+                borrow = 1;
+            }
+
+            remainder[idx + offset] = difference as u32;
+        }
+
+        let difference = remainder[idx + size] as i64 - borrow - carry as i64;
+        remainder[idx + size] = 0;
+
+        // the estimate was still one too big, add the divisor back
+        if difference < 0 {
+            estimate -= 1;
+
+            let mut carry = 0u64;
+            for (offset, limb) in divisor.iter().enumerate() {
+                let sum = remainder[idx + offset] as u64 + *limb as u64 + carry;
+                remainder[idx + offset] = (sum % BASE) as u32;
+                carry = sum / BASE;
+            }
+        }
+
+        quotient[idx] = estimate as u32;
+    }
+
+    remainder.truncate(size);
+    let (remainder, _) = divmod_small(&remainder, scale);
+    (quotient, remainder)
+}
+
+impl From<i32> for BigInt {
+    fn from(value: i32) -> BigInt {
+        BigInt::from(value as i64)
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> BigInt {
+        let mut magnitude = value.unsigned_abs();
+        let mut limbs = vec![];
+        while magnitude > 0 {
+            limbs.push((magnitude % BASE) as u32);
+            magnitude /= BASE;
+        }
+
+        BigInt::from_parts(value < 0, limbs)
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(source: &str) -> Result<BigInt, ParseBigIntError> {
+        let (negative, digits) = match source.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, source),
+        };
+
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(ParseBigIntError);
+        }
+
+        let limbs = digits
+            .as_bytes()
+            .rchunks(DIGITS_PER_LIMB)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0u32, |acc, digit| acc * 10 + (digit - b'0') as u32)
+            })
+            .collect();
+
+        Ok(BigInt::from_parts(negative, limbs))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((most_significant, rest)) = self.limbs.split_last() else {
+            return write!(f, "0");
+        };
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+
+        write!(f, "{}", most_significant)?;
+        for limb in rest.iter().rev() {
+            write!(f, "{:09}", limb)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitude(&self.limbs, &other.limbs),
+            (true, true) => compare_magnitude(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        let negative = !self.negative;
+        BigInt::from_parts(negative, self.limbs)
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_magnitude(&self.limbs, &other.limbs));
+        }
+
+        // different signs, the result has the sign of the bigger magnitude
+        match compare_magnitude(&self.limbs, &other.limbs) {
+            Ordering::Less => {
+                BigInt::from_parts(other.negative, sub_magnitude(&other.limbs, &self.limbs))
+            }
+            _ => BigInt::from_parts(self.negative, sub_magnitude(&self.limbs, &other.limbs)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other.clone()
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(
+            self.negative != other.negative,
+            mul_magnitude(&self.limbs, &other.limbs),
+        )
+    }
+}
+
+// like the primitive integers, dividing by zero panics,
+// use `BigInt::checked_div` when the divisor is unknown
+impl Div for &BigInt {
+    type Output = BigInt;
+
+    fn div(self, other: &BigInt) -> BigInt {
+        self.checked_div(other).expect("attempt to divide by zero")
+    }
+}
+
+impl Add for BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: BigInt) -> BigInt {
+        &self + &other
+    }
+}
+
+impl Sub for BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: BigInt) -> BigInt {
+        &self - &other
+    }
+}
+
+impl Mul for BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: BigInt) -> BigInt {
+        &self * &other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BigInt;
+
+    fn big(source: &str) -> BigInt {
+        source.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let tests: Vec<&'static str> = vec![
+            "0",
+            "7",
+            "-7",
+            "1000000000",
+            "123456789012345678901234567890",
+            "-999999999999999999",
+        ];
+
+        for source in tests {
+            assert_eq!(big(source).to_string(), source);
+        }
+
+        assert_eq!(big("-0"), BigInt::zero());
+        assert_eq!(big("000123").to_string(), "123");
+        assert!("".parse::<BigInt>().is_err());
+        assert!("12a".parse::<BigInt>().is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("987654321098765432109876543210");
+
+        assert_eq!((&a + &b).to_string(), "1111111110111111111011111111100");
+        assert_eq!((&a - &b).to_string(), "-864197532086419753208641975320");
+        assert_eq!(
+            (&a * &b).to_string(),
+            "121932631137021795226185032733622923332237463801111263526900"
+        );
+        assert_eq!(b.checked_div(&a).unwrap().to_string(), "8");
+        assert_eq!(
+            b.checked_rem(&a).unwrap().to_string(),
+            "9000000000900000000090"
+        );
+        assert_eq!((-b.clone()).checked_div(&a).unwrap().to_string(), "-8");
+        assert_eq!(a.checked_div(&BigInt::zero()), None);
+        assert_eq!(
+            BigInt::from(2).pow(100).to_string(),
+            "1267650600228229401496703205376"
+        );
+        assert_eq!(BigInt::from(-3).pow(3), BigInt::from(-27));
+    }
+
+    #[test]
+    fn test_long_division() {
+        let tests: Vec<(BigInt, BigInt)> = vec![
+            (
+                &BigInt::from(2).pow(2000) + &BigInt::from(1),
+                BigInt::from(3).pow(600),
+            ),
+            (
+                BigInt::from(10).pow(90),
+                &BigInt::from(10).pow(45) - &BigInt::from(1),
+            ),
+            (BigInt::from(7).pow(500), big("999999999000000001")),
+            (big("1000000000000000000000000000"), big("1000000001")),
+            (
+                big("999999999999999999999999999"),
+                big("500000000000000000"),
+            ),
+            (BigInt::from(3).pow(40), BigInt::from(3).pow(40)),
+            (BigInt::from(5), BigInt::from(3).pow(40)),
+        ];
+
+        for (dividend, divisor) in tests {
+            let quotient = dividend.checked_div(&divisor).unwrap();
+            let remainder = dividend.checked_rem(&divisor).unwrap();
+
+            assert_eq!(&(&quotient * &divisor) + &remainder, dividend);
+            assert!(!remainder.is_negative() && remainder < divisor);
+        }
+
+        assert_eq!(
+            (&BigInt::from(2).pow(64) - &BigInt::from(1))
+                .checked_div(&big("4294967297"))
+                .unwrap()
+                .to_string(),
+            "4294967295"
+        );
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(BigInt::from(i32::MAX).to_i32(), Some(i32::MAX));
+        assert_eq!(BigInt::from(i32::MIN).to_i32(), Some(i32::MIN));
+        assert_eq!(big("2147483648").to_i32(), None);
+        assert_eq!(big("1000000000000000000000").to_i32(), None);
+        assert_eq!(big("16777216").to_f64(), 16777216.0);
+        assert_eq!(big("12345678901234567890").digits(), 20);
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(big("-5") < big("3"));
+        assert!(big("-5") < big("-3"));
+        assert!(big("1000000000") > big("999999999"));
+        assert_eq!(big("42").cmp(&BigInt::from(42)), std::cmp::Ordering::Equal);
+    }
+}
@@ -0,0 +1,266 @@
+use std::fmt::Write;
+
+use crate::eval::EvaluateError;
+use crate::lexer::token::Span;
+use crate::lexer::LexerError;
+use crate::parser::ParserError;
+
+/// An error ready to be shown to the user, shared by the lexer,
+/// the parser and the evaluator
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+    /// the error the diagnostic describes, so programs can match on it
+    /// instead of on `code`, `None` when it was built with `Diagnostic::new`
+    pub kind: Option<Box<ErrorKind>>,
+}
+
+/// The step of the evaluation that failed, with its error
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    Lexer(LexerError),
+    Parser(ParserError),
+    Evaluate(EvaluateError),
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            code,
+            message: message.into(),
+            span: None,
+            notes: vec![],
+            kind: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Diagnostic {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_kind(mut self, kind: ErrorKind) -> Diagnostic {
+        self.kind = Some(Box::new(kind));
+        self
+    }
+
+    /// for the diagnostics of a source that is the line `line` of a bigger
+    /// input, makes the location point to that line when it is rendered
+    pub fn on_line(mut self, line: usize) -> Diagnostic {
+        if let Some(span) = &mut self.span {
+            span.line += line.saturating_sub(1);
+        }
+        self
+    }
+
+    /// Renders the diagnostic pointing at the offending piece of `source`, e.g:
+    ///
+    /// ```text
+    /// error[E0102]: unexpected token `*`
+    ///  --> 1:5
+    ///   |
+    /// 1 | 1 + * 2
+    ///   |     ^
+    ///   = help: expected a number, a boolean, a variable, `(`, `-` or `!`
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        self.render_from(source, None)
+    }
+
+    /// like `render`, but the location is `path:line:column`
+    /// so the error can be found in the file it comes from
+    pub fn render_in_file(&self, source: &str, path: &str) -> String {
+        self.render_from(source, Some(path))
+    }
+
+    fn render_from(&self, source: &str, path: Option<&str>) -> String {
+        let mut output = format!("error[{}]: {}\n", self.code, self.message);
+
+        let gutter = match self.span {
+            Some(span) => {
+                let gutter = " ".repeat(span.line.to_string().len());
+                let (line, caret_offset, caret_len) = locate(source, span);
+
+                // writing to a String never fails
+                let _ = match path {
+                    Some(path) => writeln!(output, "{}--> {}:{}", gutter, path, span),
+                    None => writeln!(output, "{}--> {}", gutter, span),
+                };
+                let _ = writeln!(output, "{} |", gutter);
+                let _ = writeln!(output, "{} | {}", span.line, line);
+                let _ = writeln!(
+                    output,
+                    "{} | {}{}",
+                    gutter,
+                    " ".repeat(caret_offset),
+                    "^".repeat(caret_len)
+                );
+                gutter
+            }
+            None => String::new(),
+        };
+
+        for note in &self.notes {
+            let _ = writeln!(output, "{} = {}", gutter, note);
+        }
+
+        output.trim_end().to_string()
+    }
+}
+
+/// finds the line containing the span start, returning it together
+/// with the char offset and char length of the underline
+fn locate(source: &str, span: Span) -> (&str, usize, usize) {
+    // the span may come from another source, e.g. the body of a function
+    // defined in an earlier line of the REPL, so it can't be trusted to fall
+    // on char boundaries or even inside `source`
+    let start = char_boundary(source, span.start);
+    let line_start = source[..start].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |idx| start + idx);
+
+    let line = source[line_start..line_end].trim_end_matches('\r');
+    let caret_offset = source[line_start..start].chars().count();
+
+    let end = char_boundary(source, span.end.clamp(start, line_end));
+    let caret_len = source[start..end].chars().count().max(1);
+
+    (line, caret_offset, caret_len)
+}
+
+/// the closest char boundary of `source` at or before `index`
+fn char_boundary(source: &str, index: usize) -> usize {
+    let mut index = index.min(source.len());
+    while !source.is_char_boundary(index) {
+        index -= 1;
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostic;
+    use crate::lexer::token::Span;
+
+    #[test]
+    fn test_render_with_span_and_notes() {
+        let diagnostic = Diagnostic::new("E0102", "unexpected token `*`")
+            .with_span(Span::new(4, 5, 1, 5))
+            .with_note("help: expected a number, a boolean, a variable, `(`, `-` or `!`");
+
+        let expected = [
+            "error[E0102]: unexpected token `*`",
+            " --> 1:5",
+            "  |",
+            "1 | 1 + * 2",
+            "  |     ^",
+            "  = help: expected a number, a boolean, a variable, `(`, `-` or `!`",
+        ]
+        .join("\n");
+
+        assert_eq!(diagnostic.render("1 + * 2"), expected);
+    }
+
+    #[test]
+    fn test_render_underlines_whole_span_on_its_line() {
+        let source = "let a = 1\nfoo + 1";
+        let diagnostic = Diagnostic::new("E0206", "variable `foo` is not initialized")
+            .with_note("help: declare it first with `let foo = ...`")
+            .with_span(Span::new(10, 13, 2, 1));
+
+        let expected = [
+            "error[E0206]: variable `foo` is not initialized",
+            " --> 2:1",
+            "  |",
+            "2 | foo + 1",
+            "  | ^^^",
+            "  = help: declare it first with `let foo = ...`",
+        ]
+        .join("\n");
+
+        assert_eq!(diagnostic.render(source), expected);
+    }
+
+    #[test]
+    fn test_render_in_file() {
+        let source = "let a = 1\n\nfoo + 1";
+        let diagnostic = Diagnostic::new("E0206", "variable `foo` is not initialized")
+            .with_note("help: declare it first with `let foo = ...`")
+            .with_span(Span::new(11, 14, 3, 1));
+
+        let expected = [
+            "error[E0206]: variable `foo` is not initialized",
+            " --> scripts/main.alang:3:1",
+            "  |",
+            "3 | foo + 1",
+            "  | ^^^",
+            "  = help: declare it first with `let foo = ...`",
+        ]
+        .join("\n");
+
+        assert_eq!(
+            diagnostic.render_in_file(source, "scripts/main.alang"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_render_on_line() {
+        let diagnostic = Diagnostic::new("E0206", "variable `foo` is not initialized")
+            .with_note("help: declare it first with `let foo = ...`")
+            .with_span(Span::new(0, 3, 1, 1))
+            .on_line(12);
+
+        let expected = [
+            "error[E0206]: variable `foo` is not initialized",
+            "  --> <stdin>:12:1",
+            "   |",
+            "12 | foo + 1",
+            "   | ^^^",
+            "   = help: declare it first with `let foo = ...`",
+        ]
+        .join("\n");
+
+        assert_eq!(diagnostic.render_in_file("foo + 1", "<stdin>"), expected);
+    }
+
+    #[test]
+    fn test_render_span_of_another_source() {
+        // the span falls in the middle of `é`
+        let diagnostic = Diagnostic::new("E0206", "variable `yy` is not initialized")
+            .with_span(Span::new(11, 13, 1, 12));
+
+        let expected = [
+            "error[E0206]: variable `yy` is not initialized",
+            " --> 1:12",
+            "  |",
+            "1 | let ééééééé = f()",
+            "  |        ^",
+        ]
+        .join("\n");
+        assert_eq!(diagnostic.render("let ééééééé = f()"), expected);
+
+        // or past the end of the source
+        let diagnostic = diagnostic.with_span(Span::new(40, 42, 1, 41));
+        assert!(diagnostic.render("f()").ends_with("1 | f()\n  |    ^"));
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let diagnostic = Diagnostic::new("E0104", "failed to parse all operators");
+        assert_eq!(
+            diagnostic.render("1 +"),
+            "error[E0104]: failed to parse all operators"
+        );
+    }
+}
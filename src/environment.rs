@@ -0,0 +1,402 @@
+use std::collections::HashMap;
+use std::f64::consts;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::eval::Value;
+
+#[derive(Debug, Clone)]
+struct Binding {
+    value: Value,
+    // constants are declared with `const` and can't be assigned to
+    constant: bool,
+}
+
+/// Constants declared by `Environment::with_prelude`
+const PRELUDE: &[(&str, f64)] = &[
+    ("pi", consts::PI),
+    ("e", consts::E),
+    ("tau", consts::TAU),
+    ("inf", f64::INFINITY),
+    ("nan", f64::NAN),
+];
+
+// every input evaluated by any environment gets its own number
+static INPUTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Why `Environment::assign` refused to change a variable
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssignError {
+    Undeclared,
+    Constant,
+}
+
+/// Variables of a running program, organized in nested scopes
+///
+/// Blocks push a scope that is dropped when they finish, so `let` inside
+/// them shadows outer variables only until the block is over. Calls push a
+/// frame: the function body gets a fresh scope and can only see the global
+/// variables besides its own, never the local ones of the caller. The
+/// prelude sits behind the global scope, so even a global `let` shadows it.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    // the constants declared by `with_prelude`, visible from everywhere
+    prelude: HashMap<String, Value>,
+    // the first scope holds the global variables and is never dropped
+    scopes: Vec<HashMap<String, Binding>>,
+    // index of the first scope of each running call
+    frames: Vec<usize>,
+    // expressions being evaluated one inside the other
+    nesting: usize,
+    // number of the input being evaluated, see `start_input`
+    input: usize,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment {
+            prelude: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            frames: vec![],
+            nesting: 0,
+            input: 0,
+        }
+    }
+
+    /// an environment where the mathematical constants `pi`, `e`, `tau`,
+    /// `inf` and `nan` are already declared, programs can't assign to them
+    /// but can declare their own variables with the same names
+    pub fn with_prelude() -> Environment {
+        let mut environment = Environment::new();
+        environment.prelude = PRELUDE
+            .iter()
+            .map(|(name, value)| (name.to_string(), Value::F64(*value)))
+            .collect();
+
+        environment
+    }
+
+    /// declares a global constant, programs can read it but never assign to it
+    pub fn define_constant(&mut self, name: &str, value: Value) {
+        let binding = Binding {
+            value,
+            constant: true,
+        };
+        self.scopes[0].insert(name.to_string(), binding);
+    }
+
+    fn frame_start(&self) -> usize {
+        self.frames.last().copied().unwrap_or(0)
+    }
+
+    /// indexes of the scopes visible from the current one, innermost first
+    fn visible_scopes(&self) -> impl Iterator<Item = usize> {
+        let frame_start = self.frame_start();
+        let globals = (frame_start > 0).then_some(0);
+
+        (frame_start..self.scopes.len()).rev().chain(globals)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.visible_scopes()
+            .find_map(|scope| self.scopes[scope].get(name))
+            .map(|binding| &binding.value)
+            .or_else(|| self.prelude.get(name))
+    }
+
+    /// like `get`, but the global variables are not looked up
+    pub(crate) fn get_local(&self, name: &str) -> Option<&Value> {
+        self.visible_scopes()
+            .filter(|scope| *scope != 0)
+            .find_map(|scope| self.scopes[scope].get(name))
+            .map(|binding| &binding.value)
+    }
+
+    fn bind(&mut self, name: String, value: Value, constant: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, Binding { value, constant });
+        }
+    }
+
+    /// binds `name` in the current scope, hiding any outer variable with the same name
+    pub(crate) fn declare(&mut self, name: String, value: Value) {
+        self.bind(name, value, false);
+    }
+
+    /// like `declare`, but the variable can't be assigned to afterwards
+    pub(crate) fn declare_constant(&mut self, name: String, value: Value) {
+        self.bind(name, value, true);
+    }
+
+    /// whether `name` was declared in the current scope, outer scopes are not checked
+    pub(crate) fn is_declared_in_current_scope(&self, name: &str) -> bool {
+        self.scopes
+            .last()
+            .is_some_and(|scope| scope.contains_key(name))
+    }
+
+    /// updates the closest visible variable called `name`
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), AssignError> {
+        let Some(scope) = self
+            .visible_scopes()
+            .find(|scope| self.scopes[*scope].contains_key(name))
+        else {
+            return match self.prelude.contains_key(name) {
+                true => Err(AssignError::Constant),
+                false => Err(AssignError::Undeclared),
+            };
+        };
+
+        let Some(binding) = self.scopes[scope].get_mut(name) else {
+            return Err(AssignError::Undeclared);
+        };
+
+        if binding.constant {
+            return Err(AssignError::Constant);
+        }
+
+        binding.value = value;
+        Ok(())
+    }
+
+    /// the global variables sorted by name, with whether each one is a constant,
+    /// prelude constants are included unless a global variable hides them
+    pub fn globals(&self) -> Vec<(&str, &Value, bool)> {
+        let prelude = self
+            .prelude
+            .iter()
+            .filter(|(name, _)| !self.scopes[0].contains_key(*name))
+            .map(|(name, value)| (name.as_str(), value, true));
+
+        let mut globals: Vec<_> = self.scopes[0]
+            .iter()
+            .map(|(name, binding)| (name.as_str(), &binding.value, binding.constant))
+            .chain(prelude)
+            .collect();
+        globals.sort_by_key(|(name, _, _)| *name);
+
+        globals
+    }
+
+    /// whether the global `name` is the constant declared by `with_prelude`,
+    /// and not a global variable hiding it
+    pub fn is_prelude(&self, name: &str) -> bool {
+        self.prelude.contains_key(name) && !self.scopes[0].contains_key(name)
+    }
+
+    /// forgets the global variable called `name`, returning whether there was one,
+    /// the prelude constant it was hiding is visible again. The prelude constants
+    /// themselves are never removed
+    pub fn remove_global(&mut self, name: &str) -> bool {
+        self.scopes[0].remove(name).is_some()
+    }
+
+    pub(crate) fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub(crate) fn pop_scope(&mut self) {
+        if self.scopes.len() > self.frame_start() + 1 {
+            self.scopes.pop();
+        }
+    }
+
+    pub(crate) fn push_frame(&mut self) {
+        self.frames.push(self.scopes.len());
+        self.scopes.push(HashMap::new());
+    }
+
+    /// drops every scope created since the matching `push_frame`
+    pub(crate) fn pop_frame(&mut self) {
+        if let Some(frame_start) = self.frames.pop() {
+            self.scopes.truncate(frame_start);
+        }
+    }
+
+    /// amount of calls currently running
+    pub fn call_depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// marks the start of the evaluation of a new source, the spans of the
+    /// functions defined before it point to a source that is gone
+    pub(crate) fn start_input(&mut self) {
+        self.input = INPUTS.fetch_add(1, Ordering::Relaxed) + 1;
+    }
+
+    pub(crate) fn input(&self) -> usize {
+        self.input
+    }
+
+    pub(crate) fn nesting_depth(&self) -> usize {
+        self.nesting
+    }
+
+    pub(crate) fn enter_expression(&mut self) {
+        self.nesting += 1;
+    }
+
+    pub(crate) fn leave_expression(&mut self) {
+        self.nesting = self.nesting.saturating_sub(1);
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssignError, Environment};
+    use crate::eval::Value;
+
+    #[test]
+    fn test_scopes_shadow_until_popped() {
+        let mut env = Environment::new();
+        env.declare("a".to_string(), Value::I32(1));
+
+        env.push_scope();
+        assert_eq!(env.get("a"), Some(&Value::I32(1)));
+
+        env.declare("a".to_string(), Value::I32(2));
+        env.declare("b".to_string(), Value::I32(3));
+        assert_eq!(env.get("a"), Some(&Value::I32(2)));
+
+        env.pop_scope();
+        assert_eq!(env.get("a"), Some(&Value::I32(1)));
+        assert_eq!(env.get("b"), None);
+
+        // the global scope is never dropped
+        env.pop_scope();
+        assert_eq!(env.get("a"), Some(&Value::I32(1)));
+    }
+
+    #[test]
+    fn test_assign_updates_the_closest_variable() {
+        let mut env = Environment::new();
+        env.declare("a".to_string(), Value::I32(1));
+
+        env.push_scope();
+        assert_eq!(env.assign("a", Value::I32(2)), Ok(()));
+        assert_eq!(env.assign("b", Value::I32(3)), Err(AssignError::Undeclared));
+        assert!(!env.is_declared_in_current_scope("a"));
+        env.pop_scope();
+
+        assert_eq!(env.get("a"), Some(&Value::I32(2)));
+        assert_eq!(env.get("b"), None);
+        assert!(env.is_declared_in_current_scope("a"));
+    }
+
+    #[test]
+    fn test_constants_can_not_be_assigned() {
+        let mut env = Environment::new();
+        env.declare_constant("a".to_string(), Value::I32(1));
+
+        assert_eq!(env.assign("a", Value::I32(2)), Err(AssignError::Constant));
+        assert_eq!(env.get("a"), Some(&Value::I32(1)));
+
+        // but they can be shadowed
+        env.push_scope();
+        env.declare("a".to_string(), Value::I32(3));
+        assert_eq!(env.assign("a", Value::I32(4)), Ok(()));
+        assert_eq!(env.get("a"), Some(&Value::I32(4)));
+    }
+
+    #[test]
+    fn test_prelude_constants() {
+        let mut env = Environment::with_prelude();
+        assert_eq!(env.get("pi"), Some(&Value::F64(std::f64::consts::PI)));
+        assert!(env.is_prelude("pi") && env.is_prelude("nan"));
+        assert!(!Environment::new().is_prelude("pi"));
+        assert_eq!(env.get("inf"), Some(&Value::F64(f64::INFINITY)));
+        assert_eq!(env.assign("e", Value::I32(3)), Err(AssignError::Constant));
+
+        // defined constants are global even when declared from a nested scope
+        env.push_scope();
+        env.define_constant("answer", Value::I32(42));
+        env.pop_scope();
+        assert_eq!(env.get("answer"), Some(&Value::I32(42)));
+        assert!(!env.is_prelude("answer"));
+
+        env.define_constant("pi", Value::I32(3));
+        assert!(!env.is_prelude("pi"));
+        assert_eq!(
+            env.assign("answer", Value::I32(0)),
+            Err(AssignError::Constant)
+        );
+    }
+
+    #[test]
+    fn test_globals_shadow_the_prelude() {
+        let mut env = Environment::with_prelude();
+        assert!(!env.is_declared_in_current_scope("e"));
+
+        env.declare("e".to_string(), Value::I32(3));
+        assert_eq!(env.assign("e", Value::I32(4)), Ok(()));
+        assert_eq!(env.get("e"), Some(&Value::I32(4)));
+        assert!(!env.is_prelude("e"));
+        assert!(env.globals().contains(&("e", &Value::I32(4), false)));
+
+        // forgetting the variable brings the constant back
+        assert!(env.remove_global("e"));
+        assert_eq!(env.get("e"), Some(&Value::F64(std::f64::consts::E)));
+        assert!(env.is_prelude("e"));
+        assert!(env
+            .globals()
+            .contains(&("e", &Value::F64(std::f64::consts::E), true)));
+
+        // but the constant itself stays
+        assert!(!env.remove_global("e"));
+        assert_eq!(env.get("e"), Some(&Value::F64(std::f64::consts::E)));
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut env = Environment::new();
+        env.declare("b".to_string(), Value::I32(2));
+        env.declare_constant("a".to_string(), Value::I32(1));
+        env.push_scope();
+        env.declare("local".to_string(), Value::I32(3));
+
+        assert_eq!(
+            env.globals(),
+            vec![("a", &Value::I32(1), true), ("b", &Value::I32(2), false)]
+        );
+
+        assert!(env.remove_global("b"));
+        assert!(!env.remove_global("b"));
+        assert!(!env.remove_global("local"));
+        assert_eq!(env.get("b"), None);
+        assert_eq!(env.get("local"), Some(&Value::I32(3)));
+    }
+
+    #[test]
+    fn test_frames_only_see_globals() {
+        let mut env = Environment::new();
+        env.declare("global".to_string(), Value::I32(1));
+
+        env.push_scope();
+        env.declare("local".to_string(), Value::I32(2));
+
+        assert_eq!(env.get_local("local"), Some(&Value::I32(2)));
+        assert_eq!(env.get_local("global"), None);
+
+        env.push_frame();
+        assert_eq!(env.call_depth(), 1);
+        assert_eq!(env.get("global"), Some(&Value::I32(1)));
+        assert_eq!(env.get("local"), None);
+        assert_eq!(env.get_local("local"), None);
+
+        // nested scopes of the call are dropped with it
+        env.push_scope();
+        assert_eq!(env.assign("global", Value::I32(10)), Ok(()));
+        env.declare("inner".to_string(), Value::I32(3));
+        env.pop_frame();
+
+        assert_eq!(env.call_depth(), 0);
+        assert_eq!(env.get("local"), Some(&Value::I32(2)));
+        assert_eq!(env.get("global"), Some(&Value::I32(10)));
+        assert_eq!(env.get("inner"), None);
+    }
+}
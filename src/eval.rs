@@ -1,156 +1,2735 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::cmp::Ordering;
 use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
 
-use crate::ast::{ASTNode, Operator};
+use crate::ast::{ASTNode, ASTNodeKind, Operator, Statement, StatementKind};
+use crate::bigint::BigInt;
+use crate::diagnostic::{Diagnostic, ErrorKind};
+use crate::environment::{AssignError, Environment};
+use crate::lexer::token::Span;
+use crate::rational::Rational;
+use crate::stack;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum NumericObject {
-    Declared,
+/// Result of evaluating an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// produced by statements and expressions without a value,
+    /// like assignments, loops or an `if` without `else` whose
+    /// condition is false
+    Unit,
     I32(i32),
-    F32(f32),
+    BigInt(BigInt),
+    Rational(Rational),
+    F64(f64),
+    Bool(bool),
+    /// integers from `start` up to `end`, which is only
+    /// part of the range when `inclusive` is set
+    Range {
+        start: BigInt,
+        end: BigInt,
+        inclusive: bool,
+    },
+    Function(Rc<Function>),
+    Builtin(&'static Builtin),
+    Native(Rc<NativeFunction>),
 }
 
-impl fmt::Display for NumericObject {
+/// `Value` used to be called `NumericObject`, back when every value was a number
+#[deprecated(note = "renamed to `Value`")]
+pub type NumericObject = Value;
+
+/// A function defined with `fn name(a, b) { ... }` or a closure `|a, b| ...`,
+/// which has no name. Both keep a copy of the variables they use from where
+/// they were created, except named functions that see the global ones as
+/// they change
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub name: Option<String>,
+    pub parameters: Vec<String>,
+    pub body: ASTNode,
+    pub captures: Vec<(String, Value)>,
+    // the input of `Environment` it was defined in, its spans only make
+    // sense while that input is being evaluated
+    pub(crate) input: usize,
+}
+
+impl Value {
+    /// big integers that fit in an i32 go back to `Value::I32`
+    fn from_bigint(value: BigInt) -> Value {
+        match value.to_i32() {
+            Some(value) => Value::I32(value),
+            None => Value::BigInt(value),
+        }
+    }
+
+    /// rationals with denominator 1 go back to being integers
+    fn from_rational(value: Rational) -> Value {
+        match value.is_integer() {
+            true => Value::from_bigint(value.numerator().clone()),
+            false => Value::Rational(value),
+        }
+    }
+
+    fn as_bigint(&self) -> Option<BigInt> {
+        match self {
+            Value::I32(value) => Some(BigInt::from(*value)),
+            Value::BigInt(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn as_rational(&self) -> Option<Rational> {
+        match self {
+            Value::Rational(value) => Some(value.clone()),
+            _ => self.as_bigint().map(Rational::from),
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::I32(value) => Some(*value as f64),
+            Value::BigInt(value) => Some(value.to_f64()),
+            Value::Rational(value) => Some(value.to_f64()),
+            Value::F64(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// name of the value type, used in error messages
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Unit => "unit",
+            Value::I32(_) | Value::BigInt(_) => "integer",
+            Value::Rational(_) => "rational",
+            Value::F64(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Range { .. } => "range",
+            Value::Function(_) | Value::Builtin(_) | Value::Native(_) => "function",
+        }
+    }
+
+    fn as_bool(&self, span: Span) -> Result<bool, EvaluateError> {
+        match self {
+            Value::Bool(value) => Ok(*value),
+            Value::Unit => Err(EvaluateError::VariableDoesNotHaveAValue(span)),
+            other => Err(EvaluateError::ExpectedBool(other.type_name(), span)),
+        }
+    }
+
+    /// code that evaluates back to the value, `None` for the functions
+    /// added with `Interpreter::register_fn` which only exist in rust
+    pub fn to_source(&self) -> Option<String> {
+        // negative numbers and fractions are wrapped so they stay a single operand
+        let number = |number: String| match number.starts_with('-') || number.contains('/') {
+            true => format!("({})", number),
+            false => number,
+        };
+
+        let source = match self {
+            Value::Unit => "{}".to_string(),
+            Value::I32(value) => number(value.to_string()),
+            Value::BigInt(value) => number(value.to_string()),
+            Value::Rational(value) => number(value.to_string()),
+            Value::F64(value) if value.is_nan() => "(0.0 / 0.0)".to_string(),
+            Value::F64(value) if value.is_infinite() => {
+                number(format!("{:.1} / 0.0", value.signum()))
+            }
+            // the lexer needs the dot to read a float
+            Value::F64(value) if value.fract() == 0.0 => number(format!("{}.0", value)),
+            Value::F64(value) => number(value.to_string()),
+            Value::Bool(value) => value.to_string(),
+            Value::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let operator = if *inclusive { "..=" } else { ".." };
+                format!(
+                    "({}{}{})",
+                    number(start.to_string()),
+                    operator,
+                    number(end.to_string())
+                )
+            }
+            Value::Function(function) => {
+                let parameters = function.parameters.join(", ");
+                let body = function.body.to_source();
+
+                // the captured variables are declared again for the function to copy them
+                let mut declarations = function
+                    .captures
+                    .iter()
+                    .map(|(name, value)| Some(format!("let {} = {}", name, value.to_source()?)))
+                    .collect::<Option<Vec<_>>>()?;
+
+                match &function.name {
+                    // the block declares the function, so it can still
+                    // call itself, and then produces it
+                    Some(name) => {
+                        declarations.push(format!("fn {}({}) {}", name, parameters, body));
+                        format!("{{ {}; {} }}", declarations.join("; "), name)
+                    }
+                    None if declarations.is_empty() => format!("(|{}| {})", parameters, body),
+                    None => format!(
+                        "{{ {}; (|{}| {}) }}",
+                        declarations.join("; "),
+                        parameters,
+                        body
+                    ),
+                }
+            }
+            Value::Builtin(builtin) => builtin.name.to_string(),
+            Value::Native(_) => return None,
+        };
+
+        Some(source)
+    }
+}
+
+/// formats `value` keeping only `digits` significant digits, switching
+/// to scientific notation for very small or very large numbers
+fn format_significant(value: f64, digits: usize) -> String {
+    if !value.is_finite() || value == 0.0 {
+        return value.to_string();
+    }
+
+    let digits = digits.max(1);
+    let scientific = format!("{:.*e}", digits - 1, value);
+    let Some((mantissa, exponent)) = scientific.split_once('e') else {
+        return scientific;
+    };
+    let exponent = exponent.parse::<i32>().unwrap_or(0);
+
+    let trim_zeros = |number: &str| -> String {
+        match number.contains('.') {
+            true => number
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string(),
+            false => number.to_string(),
+        }
+    };
+
+    if exponent < -4 || exponent >= digits as i32 {
+        return format!("{}e{}", trim_zeros(mantissa), exponent);
+    }
+
+    let decimals = (digits as i32 - 1 - exponent).max(0) as usize;
+    trim_zeros(&format!("{:.*}", decimals, value))
+}
+
+/// Floats honor the formatter precision as the amount of significant
+/// digits to print, e.g. `format!("{:.3}", Value::F64(1.23456))` is `1.23`
+impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            NumericObject::F32(value) => write!(f, "{}", value),
-            NumericObject::I32(value) => write!(f, "{}", value),
-            NumericObject::Declared => Ok(()),
+            Value::F64(value) => match f.precision() {
+                Some(digits) => write!(f, "{}", format_significant(*value, digits)),
+                None => write!(f, "{}", value),
+            },
+            Value::I32(value) => write!(f, "{}", value),
+            Value::BigInt(value) => write!(f, "{}", value),
+            Value::Rational(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Range {
+                start,
+                end,
+                inclusive,
+            } => match inclusive {
+                true => write!(f, "{}..={}", start, end),
+                false => write!(f, "{}..{}", start, end),
+            },
+            Value::Function(function) => match &function.name {
+                Some(name) => write!(f, "<fn {}>", name),
+                None => write!(f, "<closure>"),
+            },
+            Value::Builtin(builtin) => write!(f, "<builtin {}>", builtin.name),
+            Value::Native(native) => write!(f, "<native {}>", native.name),
+            Value::Unit => write!(f, "()"),
+        }
+    }
+}
+
+/// What to do when an operation over `Value::I32` overflows
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OverflowMode {
+    /// fails with `EvaluateError::IntegerOverflow`
+    Error,
+    /// computes the operation again using `Value::BigInt`
+    #[default]
+    Promote,
+}
+
+/// How the division of two integers behaves
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DivisionMode {
+    /// `7 / 2` is `3`
+    Truncate,
+    /// `7 / 2` is the exact `7/2`
+    #[default]
+    Rational,
+    /// `7 / 2` is `3.5`
+    Float,
+}
+
+impl FromStr for DivisionMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<DivisionMode, String> {
+        match mode {
+            "truncate" => Ok(DivisionMode::Truncate),
+            "rational" => Ok(DivisionMode::Rational),
+            "float" => Ok(DivisionMode::Float),
+            _ => Err(format!(
+                "unknown division mode `{}`, expected truncate, rational or float",
+                mode
+            )),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct EvaluationConfig {
+    pub overflow: OverflowMode,
+    pub division: DivisionMode,
+    /// significant digits used to print floats, all of them when `None`
+    pub precision: Option<usize>,
+    /// calls that can be running at the same time before failing with
+    /// `EvaluateError::CallDepthExceeded`, so runaway recursion is an error
+    /// instead of a crash
+    pub max_call_depth: usize,
+    /// expressions that can be evaluated one inside the other, counting the
+    /// ones of every running call, before failing with
+    /// `EvaluateError::NestingTooDeep`
+    pub max_nesting_depth: usize,
+    /// whether `let` can declare again a variable of the same scope,
+    /// otherwise it fails with `EvaluateError::Redeclaration`
+    pub allow_redeclaration: bool,
+}
+
+impl Default for EvaluationConfig {
+    fn default() -> Self {
+        EvaluationConfig {
+            overflow: OverflowMode::default(),
+            division: DivisionMode::default(),
+            precision: None,
+            max_call_depth: 1000,
+            max_nesting_depth: 10_000,
+            allow_redeclaration: false,
+        }
+    }
+}
+
+// big integers are unbounded, but an exponentiation producing
+// more digits than this would take ages to be computed
+const MAX_BIGINT_POW_DIGITS: f64 = 100_000.0;
+
+fn on_overflow(
+    config: &EvaluationConfig,
+    promoted: BigInt,
+    span: Span,
+) -> Result<Value, EvaluateError> {
+    match config.overflow {
+        OverflowMode::Error => Err(EvaluateError::IntegerOverflow(span)),
+        OverflowMode::Promote => Ok(Value::from_bigint(promoted)),
+    }
+}
+
+fn bigint_pow(base: &BigInt, exponent: u32, span: Span) -> Result<BigInt, EvaluateError> {
+    let magnitude = base.to_f64().abs();
+    let digits_per_exponent = match magnitude.is_finite() {
+        true => magnitude.log10(),
+        false => base.digits() as f64,
+    };
+
+    if digits_per_exponent * exponent as f64 > MAX_BIGINT_POW_DIGITS {
+        return Err(EvaluateError::IntegerOverflow(span));
+    }
+
+    Ok(base.pow(exponent))
+}
+
+fn rational_pow(base: Rational, exponent: i32, span: Span) -> Result<Value, EvaluateError> {
+    let base = match exponent < 0 {
+        true => base
+            .recip()
+            .ok_or(EvaluateError::AttemptToDivideByZero(span))?,
+        false => base,
+    };
+
+    let exponent = exponent.unsigned_abs();
+    let numerator = bigint_pow(base.numerator(), exponent, span)?;
+    let denominator = bigint_pow(base.denominator(), exponent, span)?;
+
+    match Rational::new(numerator, denominator) {
+        Some(value) => Ok(Value::from_rational(value)),
+        None => Err(EvaluateError::AttemptToDivideByZero(span)),
+    }
+}
+
+/// Both operands of a binary operation converted to the same representation
+enum NumericPair {
+    I32(i32, i32),
+    BigInt(BigInt, BigInt),
+    Rational(Rational, Rational),
+    F64(f64, f64),
+}
+
+// operands are promoted to the widest representation among them,
+// following I32 -> BigInt -> Rational -> F64
+fn promote(lhs: Value, rhs: Value, span: Span) -> Result<NumericPair, EvaluateError> {
+    for operand in [&lhs, &rhs] {
+        match operand {
+            Value::Unit => return Err(EvaluateError::VariableDoesNotHaveAValue(span)),
+            Value::I32(_) | Value::BigInt(_) | Value::Rational(_) | Value::F64(_) => {}
+            other => return Err(EvaluateError::ExpectedNumber(other.type_name(), span)),
+        }
+    }
+
+    let pair = match (&lhs, &rhs) {
+        (Value::I32(lhs), Value::I32(rhs)) => Some(NumericPair::I32(*lhs, *rhs)),
+        (Value::F64(_), _) | (_, Value::F64(_)) => lhs
+            .as_f64()
+            .zip(rhs.as_f64())
+            .map(|(lhs, rhs)| NumericPair::F64(lhs, rhs)),
+        (Value::Rational(_), _) | (_, Value::Rational(_)) => lhs
+            .as_rational()
+            .zip(rhs.as_rational())
+            .map(|(lhs, rhs)| NumericPair::Rational(lhs, rhs)),
+        _ => lhs
+            .as_bigint()
+            .zip(rhs.as_bigint())
+            .map(|(lhs, rhs)| NumericPair::BigInt(lhs, rhs)),
+    };
+
+    // every number can be promoted, so this is never an error
+    pair.ok_or(EvaluateError::VariableDoesNotHaveAValue(span))
+}
+
+/// `None` when one of the operands is NaN
+fn compare_numbers(lhs: Value, rhs: Value, span: Span) -> Result<Option<Ordering>, EvaluateError> {
+    let ordering = match promote(lhs, rhs, span)? {
+        NumericPair::I32(lhs, rhs) => lhs.partial_cmp(&rhs),
+        NumericPair::BigInt(lhs, rhs) => lhs.partial_cmp(&rhs),
+        NumericPair::Rational(lhs, rhs) => lhs.partial_cmp(&rhs),
+        NumericPair::F64(lhs, rhs) => lhs.partial_cmp(&rhs),
+    };
+
+    Ok(ordering)
+}
+
+// bools are only equal to bools, numbers are compared by value
+// regardless of their representation so `1 == 1.0` holds
+fn values_equal(lhs: Value, rhs: Value, span: Span) -> Result<bool, EvaluateError> {
+    match (&lhs, &rhs) {
+        (Value::Bool(_), _) | (_, Value::Bool(_)) => Ok(lhs.as_bool(span)? == rhs.as_bool(span)?),
+        _ => Ok(compare_numbers(lhs, rhs, span)? == Some(Ordering::Equal)),
+    }
+}
+
+fn range_bound(value: Value, span: Span) -> Result<BigInt, EvaluateError> {
+    match value {
+        Value::Unit => Err(EvaluateError::VariableDoesNotHaveAValue(span)),
+        other => other
+            .as_bigint()
+            .ok_or(EvaluateError::ExpectedInteger(other.type_name(), span)),
+    }
+}
+
+/// A function implemented in rust that every program can call, like `sqrt(2)`
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: usize,
+    // receives exactly `arity` arguments
+    function: fn(&[Value], Span, &EvaluationConfig) -> Result<Value, EvaluateError>,
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+// names are unique, so they are enough to tell builtins apart
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+type NativeFn = dyn Fn(&[Value]) -> Result<Value, String>;
+
+/// A rust closure registered by the program embedding the interpreter,
+/// see `Interpreter::register_fn`
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    // receives exactly `arity` arguments, the error message is reported
+    // to the user as `EvaluateError::NativeFunctionFailed`
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: impl Into<String>,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) -> NativeFunction {
+        NativeFunction {
+            name: name.into(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NativeFunction({})", self.name)
+    }
+}
+
+// closures can't be compared, only the very same registration is equal
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "sqrt",
+        arity: 1,
+        function: |arguments, span, _| float_function(&arguments[0], span, f64::sqrt),
+    },
+    Builtin {
+        name: "sin",
+        arity: 1,
+        function: |arguments, span, _| float_function(&arguments[0], span, f64::sin),
+    },
+    Builtin {
+        name: "cos",
+        arity: 1,
+        function: |arguments, span, _| float_function(&arguments[0], span, f64::cos),
+    },
+    Builtin {
+        name: "tan",
+        arity: 1,
+        function: |arguments, span, _| float_function(&arguments[0], span, f64::tan),
+    },
+    Builtin {
+        name: "log",
+        arity: 1,
+        function: |arguments, span, _| float_function(&arguments[0], span, f64::ln),
+    },
+    Builtin {
+        name: "abs",
+        arity: 1,
+        function: |arguments, span, config| absolute(&arguments[0], span, config),
+    },
+    Builtin {
+        name: "floor",
+        arity: 1,
+        function: |arguments, span, _| round_with(&arguments[0], span, Rational::floor, f64::floor),
+    },
+    Builtin {
+        name: "ceil",
+        arity: 1,
+        function: |arguments, span, _| round_with(&arguments[0], span, Rational::ceil, f64::ceil),
+    },
+    Builtin {
+        name: "round",
+        arity: 1,
+        function: |arguments, span, _| round_with(&arguments[0], span, Rational::round, f64::round),
+    },
+    Builtin {
+        name: "min",
+        arity: 2,
+        function: |arguments, span, _| match compare_numbers(
+            arguments[0].clone(),
+            arguments[1].clone(),
+            span,
+        )? {
+            Some(Ordering::Greater) => Ok(arguments[1].clone()),
+            Some(_) => Ok(arguments[0].clone()),
+            // one of them is NaN, whichever it is
+            None => Ok(Value::F64(f64::NAN)),
+        },
+    },
+    Builtin {
+        name: "max",
+        arity: 2,
+        function: |arguments, span, _| match compare_numbers(
+            arguments[0].clone(),
+            arguments[1].clone(),
+            span,
+        )? {
+            Some(Ordering::Less) => Ok(arguments[1].clone()),
+            Some(_) => Ok(arguments[0].clone()),
+            None => Ok(Value::F64(f64::NAN)),
+        },
+    },
+];
+
+fn find_builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+fn number_argument(value: &Value, span: Span) -> Result<f64, EvaluateError> {
+    match value {
+        Value::Unit => Err(EvaluateError::VariableDoesNotHaveAValue(span)),
+        other => other
+            .as_f64()
+            .ok_or(EvaluateError::ExpectedNumber(other.type_name(), span)),
+    }
+}
+
+// functions without exact results, like `sqrt`, always produce floats
+fn float_function(
+    value: &Value,
+    span: Span,
+    function: fn(f64) -> f64,
+) -> Result<Value, EvaluateError> {
+    Ok(Value::F64(function(number_argument(value, span)?)))
+}
+
+fn absolute(value: &Value, span: Span, config: &EvaluationConfig) -> Result<Value, EvaluateError> {
+    match value {
+        // so `abs(-0.0)` is `0` too
+        Value::F64(value) => Ok(Value::F64(value.abs())),
+        _ => match compare_numbers(value.clone(), Value::I32(0), span)? {
+            Some(Ordering::Less) => negate(value.clone(), span, config),
+            _ => Ok(value.clone()),
+        },
+    }
+}
+
+// integers are already rounded, rationals are rounded exactly into integers
+fn round_with(
+    value: &Value,
+    span: Span,
+    rational: fn(&Rational) -> BigInt,
+    float: fn(f64) -> f64,
+) -> Result<Value, EvaluateError> {
+    match value {
+        Value::I32(_) | Value::BigInt(_) => Ok(value.clone()),
+        Value::Rational(value) => Ok(Value::from_bigint(rational(value))),
+        Value::F64(value) => Ok(Value::F64(float(*value))),
+        Value::Unit => Err(EvaluateError::VariableDoesNotHaveAValue(span)),
+        other => Err(EvaluateError::ExpectedNumber(other.type_name(), span)),
+    }
+}
+
 macro_rules! check_div_or_err {
-    ($lhs:ident, $rhs:ident) => {
+    ($lhs:ident, $rhs:ident, $span:ident, $config:ident) => {
         match $lhs.checked_div($rhs) {
-            Some(value) => value,
-            None => {
-                return Err(EvaluateError::AttemptToDivideByZero);
+            Some(value) => Ok(Value::I32(value)),
+            None if $rhs == 0 => {
+                return Err(EvaluateError::AttemptToDivideByZero($span));
             }
+            // i32::MIN / -1 is the only division that does not fit in an i32
+            None => on_overflow($config, -BigInt::from($lhs), $span),
+        }
+    };
+}
+
+macro_rules! check_rational_div_or_err {
+    ($lhs:expr, $rhs:expr, $span:ident) => {
+        match $lhs.checked_div(&$rhs) {
+            Some(value) => Ok(Value::from_rational(value)),
+            None => Err(EvaluateError::AttemptToDivideByZero($span)),
         }
     };
 }
 
 macro_rules! normalize_numeric_operation {
-    ($lhs:tt / $rhs:ident) => {
-        match ($lhs, $rhs) {
-            (NumericObject::I32(lhs), NumericObject::I32(rhs)) => Ok(NumericObject::I32(check_div_or_err!(lhs, rhs))),
-            (NumericObject::F32(lhs), NumericObject::F32(rhs)) => Ok(NumericObject::F32(lhs / rhs)),
-            (NumericObject::F32(lhs), NumericObject::I32(rhs)) => {
-                return Ok(NumericObject::F32(lhs / rhs as f32));
+    ($lhs:tt / $rhs:ident, $span:ident, $config:ident) => {
+        match (promote($lhs, $rhs, $span)?, $config.division) {
+            (NumericPair::I32(lhs, rhs), DivisionMode::Truncate) => {
+                check_div_or_err!(lhs, rhs, $span, $config)
+            }
+            (NumericPair::I32(lhs, rhs), DivisionMode::Rational) => {
+                check_rational_div_or_err!(Rational::from(lhs), Rational::from(rhs), $span)
+            }
+            (NumericPair::I32(lhs, rhs), DivisionMode::Float) => {
+                Ok(Value::F64(lhs as f64 / rhs as f64))
             }
-            (NumericObject::I32(lhs), NumericObject::F32(rhs)) => {
-                return Ok(NumericObject::F32(lhs as f32 / rhs));
+            (NumericPair::BigInt(lhs, rhs), DivisionMode::Truncate) => match lhs.checked_div(&rhs) {
+                Some(value) => Ok(Value::from_bigint(value)),
+                None => Err(EvaluateError::AttemptToDivideByZero($span)),
+            },
+            (NumericPair::BigInt(lhs, rhs), DivisionMode::Rational) => {
+                check_rational_div_or_err!(Rational::from(lhs), Rational::from(rhs), $span)
             }
-            _ => return Err(EvaluateError::VariableDoesNotHaveAValue)
+            (NumericPair::BigInt(lhs, rhs), DivisionMode::Float) => {
+                Ok(Value::F64(lhs.to_f64() / rhs.to_f64()))
+            }
+            (NumericPair::Rational(lhs, rhs), _) => check_rational_div_or_err!(lhs, rhs, $span),
+            (NumericPair::F64(lhs, rhs), _) => Ok(Value::F64(lhs / rhs)),
+        }
+    };
+
+    ($lhs:ident $op:tt $rhs:ident, $checked:ident, $span:ident, $config:ident) => {
+        match promote($lhs, $rhs, $span)? {
+            NumericPair::I32(lhs, rhs) => match lhs.$checked(rhs) {
+                Some(value) => Ok(Value::I32(value)),
+                None => on_overflow($config, BigInt::from(lhs) $op BigInt::from(rhs), $span),
+            },
+            NumericPair::BigInt(lhs, rhs) => Ok(Value::from_bigint(lhs $op rhs)),
+            NumericPair::Rational(lhs, rhs) => Ok(Value::from_rational(lhs $op rhs)),
+            NumericPair::F64(lhs, rhs) => Ok(Value::F64(lhs $op rhs)),
         }
     };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvaluateError {
+    VariableDoesNotHaveAValue(Span),
+    UnexpectedUnaryOperator(Operator, Span),
+    UnexpectedBinaryOperator(Operator, Span),
+    AttemptToDivideByZero(Span),
+    ExpectedIdentifier(Span),
+    ErrUninitializedVariable(String, Span),
+    IntegerOverflow(Span),
+    ExpectedNumber(&'static str, Span),
+    ExpectedBool(&'static str, Span),
+    ExpectedInteger(&'static str, Span),
+    ExpectedRange(&'static str, Span),
+    ExpectedFunction(&'static str, Span),
+    WrongNumberOfArguments {
+        name: Option<String>,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    /// raised by `return` and caught by the call running the function, so
+    /// it only reaches the user when used outside of a function
+    Return(Value, Span),
+    CallDepthExceeded(usize, Span),
+    NestingTooDeep(usize, Span),
+    UndeclaredAssignment(String, Span),
+    AssignToConstant(String, Span),
+    Redeclaration(String, Span),
+    NativeFunctionFailed {
+        name: String,
+        message: String,
+        span: Span,
+    },
+    /// raised by the body of a function defined in an earlier input, whose
+    /// spans belong to that input, so the error points to the call instead
+    InEarlierFunction {
+        name: Option<String>,
+        error: Box<EvaluateError>,
+        span: Span,
+    },
+}
+
+impl From<EvaluateError> for Diagnostic {
+    fn from(err: EvaluateError) -> Diagnostic {
+        let kind = ErrorKind::Evaluate(err.clone());
+        let diagnostic = match err {
+            EvaluateError::VariableDoesNotHaveAValue(span) => {
+                Diagnostic::new("E0201", "expression does not have a value")
+                    .with_span(span)
+                    .with_note("note: assignments do not produce a value")
+            }
+            EvaluateError::UnexpectedUnaryOperator(op, span) => {
+                Diagnostic::new("E0202", format!("unexpected unary operator `{}`", op))
+                    .with_span(span)
+            }
+            EvaluateError::UnexpectedBinaryOperator(op, span) => {
+                Diagnostic::new("E0203", format!("unexpected binary operator `{}`", op))
+                    .with_span(span)
+            }
+            EvaluateError::AttemptToDivideByZero(span) => {
+                Diagnostic::new("E0204", "attempt to divide by zero").with_span(span)
+            }
+            EvaluateError::ExpectedIdentifier(span) => {
+                Diagnostic::new("E0205", "only variables can be assigned to")
+                    .with_span(span)
+                    .with_note("help: the left side of `=` must be a variable name")
+            }
+            EvaluateError::ErrUninitializedVariable(identifier, span) => Diagnostic::new(
+                "E0206",
+                format!("variable `{}` is not initialized", identifier),
+            )
+            .with_span(span)
+            .with_note(format!(
+                "help: declare it first with `let {} = ...`",
+                identifier
+            )),
+            EvaluateError::IntegerOverflow(span) => Diagnostic::new("E0207", "integer overflow")
+                .with_span(span)
+                .with_note("note: the result is too large to be represented as an integer"),
+            EvaluateError::ExpectedNumber(found, span) => {
+                let diagnostic = Diagnostic::new(
+                    "E0208",
+                    format!("expected a number, found {}", with_article(found)),
+                )
+                .with_span(span);
+
+                match found {
+                    "bool" => diagnostic.with_note(
+                        "note: bools can only be used with `==`, `!=`, `&&`, `||` and `!`",
+                    ),
+                    _ => diagnostic,
+                }
+            }
+            EvaluateError::ExpectedBool(found, span) => Diagnostic::new(
+                "E0209",
+                format!("expected a bool, found {}", with_article(found)),
+            )
+            .with_span(span)
+            .with_note("help: use a comparison like `x != 0` to get a bool"),
+            EvaluateError::ExpectedInteger(found, span) => Diagnostic::new(
+                "E0210",
+                format!("expected an integer, found {}", with_article(found)),
+            )
+            .with_span(span)
+            .with_note("note: ranges can only be built from integers"),
+            EvaluateError::ExpectedRange(found, span) => Diagnostic::new(
+                "E0211",
+                format!("expected a range, found {}", with_article(found)),
+            )
+            .with_span(span)
+            .with_note("help: loops look like `for i in 0..10 { ... }`"),
+            EvaluateError::ExpectedFunction(found, span) => Diagnostic::new(
+                "E0212",
+                format!("expected a function, found {}", with_article(found)),
+            )
+            .with_span(span)
+            .with_note("note: only functions, closures and builtins like `sqrt` can be called"),
+            EvaluateError::WrongNumberOfArguments {
+                name,
+                expected,
+                found,
+                span,
+            } => {
+                let callee = match name {
+                    Some(name) => format!("function `{}`", name),
+                    None => "closure".to_string(),
+                };
+
+                Diagnostic::new(
+                    "E0213",
+                    format!(
+                        "{} takes {} argument(s) but {} were given",
+                        callee, expected, found
+                    ),
+                )
+                .with_span(span)
+            }
+            EvaluateError::Return(_, span) => {
+                Diagnostic::new("E0214", "`return` outside of a function").with_span(span)
+            }
+            EvaluateError::CallDepthExceeded(depth, span) => {
+                Diagnostic::new("E0215", format!("more than {} nested calls", depth))
+                    .with_span(span)
+                    .with_note(
+                        "help: check that recursive functions eventually stop calling themselves",
+                    )
+            }
+            EvaluateError::NestingTooDeep(depth, span) => {
+                Diagnostic::new("E0220", format!("more than {} nested expressions", depth))
+                    .with_span(span)
+                    .with_note("note: this usually comes from deeply recursive functions")
+            }
+            EvaluateError::UndeclaredAssignment(identifier, span) => Diagnostic::new(
+                "E0216",
+                format!("cannot assign to undeclared variable `{}`", identifier),
+            )
+            .with_span(span)
+            .with_note(format!(
+                "help: declare it first with `let {} = ...`",
+                identifier
+            )),
+            EvaluateError::AssignToConstant(identifier, span) => Diagnostic::new(
+                "E0217",
+                format!("cannot assign twice to constant `{}`", identifier),
+            )
+            .with_span(span)
+            .with_note(format!(
+                "help: declare it with `let {} = ...` to make it changeable",
+                identifier
+            )),
+            EvaluateError::NativeFunctionFailed {
+                name,
+                message,
+                span,
+            } => {
+                Diagnostic::new("E0219", format!("`{}` failed: {}", name, message)).with_span(span)
+            }
+            EvaluateError::InEarlierFunction { name, error, span } => {
+                let function = match name {
+                    Some(name) => format!("`{}`", name),
+                    None => "a closure".to_string(),
+                };
+
+                Diagnostic::from(*error).with_span(span).with_note(format!(
+                    "note: raised inside {}, defined in an earlier input",
+                    function
+                ))
+            }
+            EvaluateError::Redeclaration(identifier, span) => Diagnostic::new(
+                "E0218",
+                format!("`{}` is already declared in this scope", identifier),
+            )
+            .with_span(span)
+            .with_note(format!(
+                "help: use `{} = ...` to change its value",
+                identifier
+            )),
+        };
+
+        diagnostic.with_kind(kind)
+    }
+}
+
+/// the name of a type returned by `Value::type_name` with its article,
+/// like `an integer`
+fn with_article(type_name: &str) -> String {
+    // `unit` sounds like it starts with a consonant
+    match type_name.chars().next() {
+        Some('a' | 'e' | 'i' | 'o') => format!("an {}", type_name),
+        _ => format!("a {}", type_name),
+    }
+}
+
+/// runs the statements producing the value of the last one, `Value::Unit`
+/// when there are no statements or the last one has no value
+pub fn evaluate(
+    statements: &[Statement],
+    evaluation_env: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    evaluation_env.start_input();
+    execute_statements(statements, evaluation_env, config)
+}
+
+/// runs the statements in order producing the value of the last one,
+/// no statements at all produce no value
+fn execute_statements(
+    statements: &[Statement],
+    evaluation_env: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    let mut value = Value::Unit;
+    for statement in statements {
+        value = execute_statement(statement, evaluation_env, config)?;
+    }
+
+    Ok(value)
+}
+
+fn execute_statement(
+    statement: &Statement,
+    evaluation_env: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    match &statement.kind {
+        StatementKind::Expression(expression) => {
+            evaluate_expression(expression, evaluation_env, config)
+        }
+        StatementKind::Let {
+            name,
+            value,
+            constant,
+        } => {
+            check_redeclaration(name, statement.span, evaluation_env, config)?;
 
-    ($lhs:ident $op:tt $rhs:ident) => {
-        match ($lhs, $rhs) {
-            (NumericObject::I32(lhs), NumericObject::I32(rhs)) => Ok(NumericObject::I32(lhs $op rhs)),
-            (NumericObject::F32(lhs), NumericObject::F32(rhs)) => Ok(NumericObject::F32(lhs $op rhs)),
-            (NumericObject::F32(lhs), NumericObject::I32(rhs)) => {
-                return Ok(NumericObject::F32(lhs $op rhs as f32));
+            let value = evaluate_expression(value, evaluation_env, config)?;
+            match constant {
+                true => evaluation_env.declare_constant(name.clone(), value),
+                false => evaluation_env.declare(name.clone(), value),
             }
-            (NumericObject::I32(lhs), NumericObject::F32(rhs)) => {
-                return Ok(NumericObject::F32(lhs as f32 $op rhs));
+            Ok(Value::Unit)
+        }
+        StatementKind::While { condition, body } => {
+            while evaluate_expression(condition, evaluation_env, config)?.as_bool(condition.span)? {
+                evaluate_expression(body, evaluation_env, config)?;
             }
-            _ => return Err(EvaluateError::VariableDoesNotHaveAValue)
+
+            // loops are run for their effects on the variables
+            Ok(Value::Unit)
+        }
+        StatementKind::For {
+            variable,
+            iterable,
+            body,
+        } => execute_for(variable, iterable, body, evaluation_env, config),
+        StatementKind::Function {
+            name,
+            parameters,
+            body,
+        } => {
+            check_redeclaration(name, statement.span, evaluation_env, config)?;
+
+            // named functions see the global variables as they change, their
+            // parameters and themselves, so they can call themselves recursively,
+            // while the local variables they use are copied like in closures
+            let captures =
+                collect_captures(parameters, body, |identifier| match identifier == name {
+                    true => None,
+                    false => evaluation_env.get_local(identifier).cloned(),
+                });
+
+            let function = Function {
+                name: Some(name.clone()),
+                parameters: parameters.clone(),
+                body: body.clone(),
+                captures,
+                input: evaluation_env.input(),
+            };
+
+            evaluation_env.declare(name.clone(), Value::Function(Rc::new(function)));
+            Ok(Value::Unit)
+        }
+        StatementKind::Return(value) => {
+            let value = match value {
+                Some(value) => evaluate_expression(value, evaluation_env, config)?,
+                None => Value::Unit,
+            };
+
+            Err(EvaluateError::Return(value, statement.span))
+        }
+    }
+}
+
+fn check_redeclaration(
+    name: &str,
+    span: Span,
+    evaluation_env: &Environment,
+    config: &EvaluationConfig,
+) -> Result<(), EvaluateError> {
+    match !config.allow_redeclaration && evaluation_env.is_declared_in_current_scope(name) {
+        true => Err(EvaluateError::Redeclaration(name.to_string(), span)),
+        false => Ok(()),
+    }
+}
+
+fn execute_for(
+    variable: &str,
+    iterable: &ASTNode,
+    body: &ASTNode,
+    evaluation_env: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    let (start, end, inclusive) = match evaluate_expression(iterable, evaluation_env, config)? {
+        Value::Range {
+            start,
+            end,
+            inclusive,
+        } => (start, end, inclusive),
+        Value::Unit => return Err(EvaluateError::VariableDoesNotHaveAValue(iterable.span)),
+        other => {
+            return Err(EvaluateError::ExpectedRange(
+                other.type_name(),
+                iterable.span,
+            ))
         }
     };
+
+    // the loop variable lives in its own scope, a variable
+    // with the same name is hidden until the loop is over
+    evaluation_env.push_scope();
+
+    let mut outcome = Ok(());
+    let mut current = start;
+    while current < end || (inclusive && current == end) {
+        evaluation_env.declare(variable.to_string(), Value::from_bigint(current.clone()));
+        outcome = evaluate_expression(body, evaluation_env, config).map(|_| ());
+        if outcome.is_err() {
+            break;
+        }
+
+        current = &current + &BigInt::from(1);
+    }
+
+    evaluation_env.pop_scope();
+
+    outcome.map(|_| Value::Unit)
 }
 
-#[derive(Debug)]
-pub enum EvaluateError {
-    VariableDoesNotHaveAValue,
-    UnexpectedUnaryOperator,
-    UnexpectedBinaryOperator,
-    AttemptToDivideByZero,
-    ExpectedIdentifier,
-    ErrUninitializedVariable,
-}
-
-pub fn evaluate(expression_tree: ASTNode, evaluation_env: &mut HashMap<String, NumericObject>) {
-    let result = evaluate_expression(expression_tree, evaluation_env);
-    match result {
-        Ok(numeric_object_value) => match numeric_object_value {
-            NumericObject::Declared => {}
-            _ => println!("{}", numeric_object_value),
-        },
-        Err(err) => println!("Evaluation error: {:?}", err),
+fn evaluate_call(
+    callee: &ASTNode,
+    arguments: &[ASTNode],
+    span: Span,
+    evaluation_env: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    let callee_value = evaluate_expression(callee, evaluation_env, config)?;
+    let (name, arity) = match &callee_value {
+        Value::Function(function) => (function.name.clone(), function.parameters.len()),
+        Value::Builtin(builtin) => (Some(builtin.name.to_string()), builtin.arity),
+        Value::Native(native) => (Some(native.name.clone()), native.arity),
+        Value::Unit => return Err(EvaluateError::VariableDoesNotHaveAValue(callee.span)),
+        other => {
+            return Err(EvaluateError::ExpectedFunction(
+                other.type_name(),
+                callee.span,
+            ))
+        }
+    };
+
+    if arity != arguments.len() {
+        return Err(EvaluateError::WrongNumberOfArguments {
+            name,
+            expected: arity,
+            found: arguments.len(),
+            span,
+        });
+    }
+
+    let mut values = Vec::with_capacity(arguments.len());
+    for argument in arguments {
+        values.push(evaluate_expression(argument, evaluation_env, config)?);
+    }
+
+    match callee_value {
+        Value::Builtin(builtin) => (builtin.function)(&values, span, config),
+        Value::Native(native) => {
+            (native.function)(&values).map_err(|message| EvaluateError::NativeFunctionFailed {
+                name: native.name.clone(),
+                message,
+                span,
+            })
+        }
+        Value::Function(function) => {
+            if evaluation_env.call_depth() >= config.max_call_depth {
+                return Err(EvaluateError::CallDepthExceeded(
+                    config.max_call_depth,
+                    span,
+                ));
+            }
+
+            call_function(&function, values, span, evaluation_env, config)
+        }
+        // every other value was rejected above
+        other => Err(EvaluateError::ExpectedFunction(
+            other.type_name(),
+            callee.span,
+        )),
+    }
+}
+
+fn create_closure(parameters: &[String], body: &ASTNode, evaluation_env: &Environment) -> Value {
+    let captures = collect_captures(parameters, body, |identifier| {
+        evaluation_env.get(identifier).cloned()
+    });
+
+    Value::Function(Rc::new(Function {
+        name: None,
+        parameters: parameters.to_vec(),
+        body: body.clone(),
+        captures,
+        input: evaluation_env.input(),
+    }))
+}
+
+/// the variables used by `body` that `lookup` finds, besides the parameters,
+/// they are copied so later changes to them are not seen by the function
+fn collect_captures(
+    parameters: &[String],
+    body: &ASTNode,
+    lookup: impl Fn(&str) -> Option<Value>,
+) -> Vec<(String, Value)> {
+    let mut identifiers: Vec<&str> = vec![];
+    collect_identifiers(body, &mut identifiers);
+
+    let mut captures: Vec<(String, Value)> = vec![];
+    for identifier in identifiers {
+        let already_captured = captures.iter().any(|(name, _)| name == identifier);
+        if already_captured || parameters.iter().any(|parameter| parameter == identifier) {
+            continue;
+        }
+
+        if let Some(value) = lookup(identifier) {
+            captures.push((identifier.to_string(), value));
+        }
     }
+
+    captures
 }
 
+/// every variable name used in the expression, so closures know what to capture
+fn collect_identifiers<'a>(expression_tree: &'a ASTNode, identifiers: &mut Vec<&'a str>) {
+    match &expression_tree.kind {
+        ASTNodeKind::Ident(identifier) => identifiers.push(identifier),
+        ASTNodeKind::I32(_)
+        | ASTNodeKind::BigInt(_)
+        | ASTNodeKind::F64(_)
+        | ASTNodeKind::Bool(_) => {}
+        ASTNodeKind::UnaryExpr { inner, .. } => collect_identifiers(inner, identifiers),
+        ASTNodeKind::BinaryExpr { lhs, rhs, .. } => {
+            stack::grow(|| collect_identifiers(lhs, identifiers));
+            stack::grow(|| collect_identifiers(rhs, identifiers));
+        }
+        ASTNodeKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            collect_identifiers(condition, identifiers);
+            collect_identifiers(then_branch, identifiers);
+            if let Some(else_branch) = else_branch {
+                collect_identifiers(else_branch, identifiers);
+            }
+        }
+        ASTNodeKind::Block(statements) => {
+            for statement in statements {
+                match &statement.kind {
+                    StatementKind::Expression(expression) => {
+                        collect_identifiers(expression, identifiers)
+                    }
+                    StatementKind::Let { value, .. } => collect_identifiers(value, identifiers),
+                    StatementKind::While { condition, body } => {
+                        collect_identifiers(condition, identifiers);
+                        collect_identifiers(body, identifiers);
+                    }
+                    StatementKind::For { iterable, body, .. } => {
+                        collect_identifiers(iterable, identifiers);
+                        collect_identifiers(body, identifiers);
+                    }
+                    StatementKind::Function { body, .. } => collect_identifiers(body, identifiers),
+                    StatementKind::Return(value) => {
+                        if let Some(value) = value {
+                            collect_identifiers(value, identifiers);
+                        }
+                    }
+                }
+            }
+        }
+        ASTNodeKind::Call { callee, arguments } => {
+            collect_identifiers(callee, identifiers);
+            for argument in arguments {
+                collect_identifiers(argument, identifiers);
+            }
+        }
+        ASTNodeKind::Closure { body, .. } => collect_identifiers(body, identifiers),
+    }
+}
+
+/// runs the function body in a new frame, where only the global variables
+/// are visible besides the function itself, its captures and its parameters
+fn call_function(
+    function: &Rc<Function>,
+    arguments: Vec<Value>,
+    span: Span,
+    evaluation_env: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    evaluation_env.push_frame();
+
+    if let Some(name) = &function.name {
+        evaluation_env.declare(name.clone(), Value::Function(Rc::clone(function)));
+    }
+
+    // parameters are bound after the captures so they win on a name clash
+    let bindings = function
+        .captures
+        .iter()
+        .cloned()
+        .chain(function.parameters.iter().cloned().zip(arguments));
+    for (parameter, argument) in bindings {
+        evaluation_env.declare(parameter, argument);
+    }
+
+    let outcome = match evaluate_expression(&function.body, evaluation_env, config) {
+        Err(EvaluateError::Return(value, _)) => Ok(value),
+        // only the outermost call from the current input is worth pointing to
+        Err(EvaluateError::InEarlierFunction { error, .. })
+            if function.input != evaluation_env.input() =>
+        {
+            Err(EvaluateError::InEarlierFunction {
+                name: function.name.clone(),
+                error,
+                span,
+            })
+        }
+        Err(error) if function.input != evaluation_env.input() => {
+            Err(EvaluateError::InEarlierFunction {
+                name: function.name.clone(),
+                error: Box::new(error),
+                span,
+            })
+        }
+        outcome => outcome,
+    };
+
+    evaluation_env.pop_frame();
+    outcome
+}
+
+/// every nested expression and call goes through here, so it is where the
+/// nesting is limited and the stack grows before it could overflow
 fn evaluate_expression(
-    expression_tree: ASTNode,
-    evaluation_env: &mut HashMap<String, NumericObject>,
-) -> Result<NumericObject, EvaluateError> {
-    match expression_tree {
-        ASTNode::Ident(identifier) => match evaluation_env.get(&identifier) {
-            Some(value) => Ok(value.to_owned()),
-            None => Err(EvaluateError::ErrUninitializedVariable),
-        },
-        ASTNode::I32(value) => Ok(NumericObject::I32(value)),
-        ASTNode::F32(value) => Ok(NumericObject::F32(value)),
-        ASTNode::UnaryExpr { op, inner } => match op {
+    expression_tree: &ASTNode,
+    evaluation_env: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    if evaluation_env.nesting_depth() >= config.max_nesting_depth {
+        return Err(EvaluateError::NestingTooDeep(
+            config.max_nesting_depth,
+            expression_tree.span,
+        ));
+    }
+
+    evaluation_env.enter_expression();
+    let outcome = stack::grow(|| evaluate_node(expression_tree, evaluation_env, config));
+    evaluation_env.leave_expression();
+
+    outcome
+}
+
+// the work of each node lives in its own function so the stack frame of
+// `evaluate_node`, used on every nested expression and call, stays small
+fn evaluate_node(
+    expression_tree: &ASTNode,
+    evaluation_env: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    let span = expression_tree.span;
+
+    match &expression_tree.kind {
+        // variables hide the builtins with the same name
+        ASTNodeKind::Ident(identifier) => {
+            match (evaluation_env.get(identifier), find_builtin(identifier)) {
+                (Some(value), _) => Ok(value.to_owned()),
+                (None, Some(builtin)) => Ok(Value::Builtin(builtin)),
+                (None, None) => Err(EvaluateError::ErrUninitializedVariable(
+                    identifier.clone(),
+                    span,
+                )),
+            }
+        }
+        ASTNodeKind::I32(value) => Ok(Value::I32(*value)),
+        ASTNodeKind::BigInt(value) => Ok(Value::from_bigint(value.clone())),
+        ASTNodeKind::F64(value) => Ok(Value::F64(*value)),
+        ASTNodeKind::Bool(value) => Ok(Value::Bool(*value)),
+        ASTNodeKind::Block(statements) => {
+            evaluation_env.push_scope();
+            let outcome = execute_statements(statements, evaluation_env, config);
+            evaluation_env.pop_scope();
+            outcome
+        }
+        ASTNodeKind::Call { callee, arguments } => {
+            evaluate_call(callee, arguments, span, evaluation_env, config)
+        }
+        ASTNodeKind::Closure { parameters, body } => {
+            Ok(create_closure(parameters, body, evaluation_env))
+        }
+        ASTNodeKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let condition_span = condition.span;
+            let condition = evaluate_expression(condition, evaluation_env, config)?;
+
+            match (condition.as_bool(condition_span)?, else_branch) {
+                (true, _) => evaluate_expression(then_branch, evaluation_env, config),
+                (false, Some(else_branch)) => {
+                    evaluate_expression(else_branch, evaluation_env, config)
+                }
+                // without an else branch there is no value to produce
+                (false, None) => Ok(Value::Unit),
+            }
+        }
+        ASTNodeKind::UnaryExpr { op, inner } => match op {
             Operator::Negative => {
-                let evaluated_object = evaluate_expression(*inner, evaluation_env).unwrap();
-                let negative = match evaluated_object {
-                    NumericObject::F32(value) => NumericObject::F32(-value),
-                    NumericObject::I32(value) => NumericObject::I32(-value),
-                    NumericObject::Declared => {
-                        return Err(EvaluateError::VariableDoesNotHaveAValue)
-                    }
-                };
-                Ok(negative)
+                let evaluated_object = evaluate_expression(inner, evaluation_env, config)?;
+                negate(evaluated_object, span, config)
+            }
+            Operator::Not => {
+                let evaluated_object = evaluate_expression(inner, evaluation_env, config)?;
+                Ok(Value::Bool(!evaluated_object.as_bool(span)?))
             }
-            _ => Err(EvaluateError::UnexpectedUnaryOperator),
+            _ => Err(EvaluateError::UnexpectedUnaryOperator(*op, span)),
         },
-        ASTNode::BinaryExpr { op, lhs, rhs } => {
-            match op {
-                Operator::Assign => match lhs.as_ref() {
-                    ASTNode::Ident(value) => {
-                        let rhs = evaluate_expression(*rhs, evaluation_env).unwrap();
-                        evaluation_env.insert(value.clone(), rhs);
-                        return Ok(NumericObject::Declared);
+        ASTNodeKind::BinaryExpr { op, lhs, rhs } => {
+            let op = *op;
+            if op == Operator::Assign {
+                return match &lhs.kind {
+                    ASTNodeKind::Ident(value) => {
+                        let rhs = evaluate_expression(rhs, evaluation_env, config)?;
+                        match evaluation_env.assign(value, rhs) {
+                            Ok(()) => Ok(Value::Unit),
+                            Err(AssignError::Undeclared) => {
+                                Err(EvaluateError::UndeclaredAssignment(value.clone(), lhs.span))
+                            }
+                            Err(AssignError::Constant) => {
+                                Err(EvaluateError::AssignToConstant(value.clone(), lhs.span))
+                            }
+                        }
                     }
-                    _ => return Err(EvaluateError::ExpectedIdentifier),
-                },
-                _ => {}
+                    _ => Err(EvaluateError::ExpectedIdentifier(lhs.span)),
+                };
             }
 
-            let lhs = evaluate_expression(*lhs, evaluation_env).unwrap();
-            let rhs = evaluate_expression(*rhs, evaluation_env).unwrap();
+            evaluate_chain(expression_tree, evaluation_env, config)
+        }
+    }
+}
 
-            match op {
-                Operator::Plus => normalize_numeric_operation!(lhs + rhs),
-                Operator::Minus => normalize_numeric_operation!(lhs - rhs),
-                Operator::Multiplication => normalize_numeric_operation!(lhs * rhs),
-                Operator::Division => normalize_numeric_operation!(lhs / rhs),
-                Operator::Exponential => match (lhs, rhs) {
-                    (NumericObject::Declared, _) | (_, NumericObject::Declared) => {
-                        return Err(EvaluateError::VariableDoesNotHaveAValue)
-                    }
+/// `1 + 2 + 3` is `(1 + 2) + 3`, so chains of operators go deep on the left
+/// side, which is walked with a loop to not count the chain as nested
+fn evaluate_chain(
+    expression_tree: &ASTNode,
+    evaluation_env: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    let mut chain: Vec<(Operator, &ASTNode, Span)> = vec![];
+    let mut first = expression_tree;
+    while let ASTNodeKind::BinaryExpr { op, lhs, rhs } = &first.kind {
+        if *op == Operator::Assign {
+            break;
+        }
 
-                    (NumericObject::I32(lhs), NumericObject::I32(rhs)) => {
-                        Ok(NumericObject::I32(lhs.pow(rhs as u32)))
-                    }
-                    (NumericObject::F32(lhs), NumericObject::F32(rhs)) => {
-                        Ok(NumericObject::F32(lhs.powf(rhs)))
+        chain.push((*op, rhs, first.span));
+        first = lhs;
+    }
+
+    let mut lhs = evaluate_expression(first, evaluation_env, config)?;
+    for (op, rhs, span) in chain.into_iter().rev() {
+        lhs = match op {
+            // `&&` and `||` only evaluate the right side when needed
+            Operator::And | Operator::Or => {
+                let value = lhs.as_bool(span)?;
+                if value == (op == Operator::Or) {
+                    Value::Bool(value)
+                } else {
+                    let rhs = evaluate_expression(rhs, evaluation_env, config)?;
+                    Value::Bool(rhs.as_bool(span)?)
+                }
+            }
+            _ => {
+                let rhs = evaluate_expression(rhs, evaluation_env, config)?;
+                apply_binary_operator(op, lhs, rhs, span, config)?
+            }
+        };
+    }
+
+    Ok(lhs)
+}
+
+fn negate(value: Value, span: Span, config: &EvaluationConfig) -> Result<Value, EvaluateError> {
+    let negative = match value {
+        Value::F64(value) => Value::F64(-value),
+        Value::I32(value) => match value.checked_neg() {
+            Some(negative) => Value::I32(negative),
+            None => on_overflow(config, -BigInt::from(value), span)?,
+        },
+        Value::BigInt(value) => Value::from_bigint(-value),
+        Value::Rational(value) => Value::from_rational(-value),
+        Value::Unit => return Err(EvaluateError::VariableDoesNotHaveAValue(span)),
+        other => return Err(EvaluateError::ExpectedNumber(other.type_name(), span)),
+    };
+    Ok(negative)
+}
+
+/// the powers of 0, 1 and -1, which stay small however big the exponent
+/// is, `None` for other bases and for 0 to a negative exponent
+fn small_base_pow(base: &BigInt, exponent: &BigInt) -> Option<Value> {
+    match base.to_i32()? {
+        1 => Some(Value::I32(1)),
+        -1 => {
+            let odd = !exponent.checked_rem(&BigInt::from(2))?.is_zero();
+            Some(Value::I32(if odd { -1 } else { 1 }))
+        }
+        0 if exponent.is_zero() => Some(Value::I32(1)),
+        0 if !exponent.is_negative() => Some(Value::I32(0)),
+        _ => None,
+    }
+}
+
+fn apply_binary_operator(
+    op: Operator,
+    lhs: Value,
+    rhs: Value,
+    span: Span,
+    config: &EvaluationConfig,
+) -> Result<Value, EvaluateError> {
+    match op {
+        Operator::Plus => {
+            normalize_numeric_operation!(lhs + rhs, checked_add, span, config)
+        }
+        Operator::Minus => {
+            normalize_numeric_operation!(lhs - rhs, checked_sub, span, config)
+        }
+        Operator::Multiplication => {
+            normalize_numeric_operation!(lhs * rhs, checked_mul, span, config)
+        }
+        Operator::Division => normalize_numeric_operation!(lhs / rhs, span, config),
+        Operator::Exponential => {
+            match promote(lhs, rhs, span)? {
+                // a negative exponent can't produce an integer
+                NumericPair::I32(lhs, rhs) if rhs < 0 => match config.division {
+                    DivisionMode::Rational => rational_pow(Rational::from(lhs), rhs, span),
+                    _ => Ok(Value::F64((lhs as f64).powi(rhs))),
+                },
+                NumericPair::I32(lhs, rhs) => match lhs.checked_pow(rhs as u32) {
+                    Some(value) => Ok(Value::I32(value)),
+                    None => {
+                        let promoted = bigint_pow(&BigInt::from(lhs), rhs as u32, span)?;
+                        on_overflow(config, promoted, span)
                     }
-                    (NumericObject::I32(lhs), NumericObject::F32(rhs)) => {
-                        Ok(NumericObject::F32((lhs as f32).powf(rhs)))
+                },
+                NumericPair::BigInt(lhs, rhs) => match (rhs.to_i32(), config.division) {
+                    (Some(exponent), _) if exponent >= 0 => {
+                        Ok(Value::from_bigint(bigint_pow(&lhs, exponent as u32, span)?))
                     }
-                    (NumericObject::F32(lhs), NumericObject::I32(rhs)) => {
-                        Ok(NumericObject::F32(lhs.powi(rhs)))
+                    (Some(exponent), DivisionMode::Rational) => {
+                        rational_pow(Rational::from(lhs), exponent, span)
                     }
+                    (_, _) => match small_base_pow(&lhs, &rhs) {
+                        Some(value) => Ok(value),
+                        None if rhs.is_negative() => {
+                            Ok(Value::F64(lhs.to_f64().powf(rhs.to_f64())))
+                        }
+                        None => Err(EvaluateError::IntegerOverflow(span)),
+                    },
                 },
-                _ => Err(EvaluateError::UnexpectedBinaryOperator),
+                // only integer exponents keep the result exact
+                NumericPair::Rational(lhs, rhs) => {
+                    match rhs.is_integer().then(|| rhs.numerator().to_i32()).flatten() {
+                        Some(exponent) => rational_pow(lhs, exponent, span),
+                        None => Ok(Value::F64(lhs.to_f64().powf(rhs.to_f64()))),
+                    }
+                }
+                NumericPair::F64(lhs, rhs) => Ok(Value::F64(lhs.powf(rhs))),
             }
         }
+        Operator::Range | Operator::RangeInclusive => Ok(Value::Range {
+            start: range_bound(lhs, span)?,
+            end: range_bound(rhs, span)?,
+            inclusive: op == Operator::RangeInclusive,
+        }),
+        Operator::Equal => Ok(Value::Bool(values_equal(lhs, rhs, span)?)),
+        Operator::NotEqual => Ok(Value::Bool(!values_equal(lhs, rhs, span)?)),
+        Operator::Less => Ok(Value::Bool(matches!(
+            compare_numbers(lhs, rhs, span)?,
+            Some(Ordering::Less)
+        ))),
+        Operator::LessEqual => Ok(Value::Bool(matches!(
+            compare_numbers(lhs, rhs, span)?,
+            Some(Ordering::Less | Ordering::Equal)
+        ))),
+        Operator::Greater => Ok(Value::Bool(matches!(
+            compare_numbers(lhs, rhs, span)?,
+            Some(Ordering::Greater)
+        ))),
+        Operator::GreaterEqual => Ok(Value::Bool(matches!(
+            compare_numbers(lhs, rhs, span)?,
+            Some(Ordering::Greater | Ordering::Equal)
+        ))),
+        _ => Err(EvaluateError::UnexpectedBinaryOperator(op, span)),
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::panic;
+
+    use super::{
+        execute_statements, BigInt, DivisionMode, EvaluateError, EvaluationConfig, OverflowMode,
+        Rational, Value,
+    };
+    use crate::{diagnostic::Diagnostic, environment::Environment, lexer, parser};
+
+    fn run_with(
+        source: &str,
+        evaluation_env: &mut Environment,
+        config: &EvaluationConfig,
+    ) -> Result<Value, Diagnostic> {
+        let tokens = lexer::extract_token_stream(source.to_string())?;
+        let statements = parser::parse(tokens)?;
+        Ok(execute_statements(&statements, evaluation_env, config)?)
+    }
+
+    fn run(source: &str, evaluation_env: &mut Environment) -> Result<Value, Diagnostic> {
+        run_with(source, evaluation_env, &EvaluationConfig::default())
+    }
+
+    fn eval_err_with(source: &str, config: &EvaluationConfig) -> EvaluateError {
+        let tokens = lexer::extract_token_stream(source.to_string()).unwrap();
+        let statements = parser::parse(tokens).unwrap();
+        execute_statements(&statements, &mut Environment::new(), config).unwrap_err()
+    }
+
+    fn eval_err(source: &str) -> EvaluateError {
+        eval_err_with(source, &EvaluationConfig::default())
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            ("1 + 1", Value::I32(2)),
+            ("-1 + 1", Value::I32(0)),
+            ("5 * 2 / 2", Value::I32(5)),
+            ("10 / (3 + 2)", Value::I32(2)),
+            ("2 ^ 3", Value::I32(8)),
+            ("1.5 + 1", Value::F64(2.5)),
+            ("10 - 5 - 2", Value::I32(3)),
+            ("2 ^ 3 ^ 2", Value::I32(512)),
+            ("let a = 2", Value::Unit),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+    }
+
+    #[test]
+    fn test_undefined_operands_are_errors() {
+        assert!(matches!(
+            eval_err("-x"),
+            EvaluateError::ErrUninitializedVariable(name, _) if name == "x"
+        ));
+        assert!(matches!(
+            eval_err("let a = -b"),
+            EvaluateError::ErrUninitializedVariable(name, _) if name == "b"
+        ));
+        assert!(matches!(
+            eval_err("x + 1"),
+            EvaluateError::ErrUninitializedVariable(name, _) if name == "x"
+        ));
+        assert!(matches!(
+            eval_err("1 + y"),
+            EvaluateError::ErrUninitializedVariable(name, _) if name == "y"
+        ));
+        assert!(matches!(
+            eval_err("let a = 0; -(a = 1)"),
+            EvaluateError::VariableDoesNotHaveAValue(_)
+        ));
+        assert!(matches!(
+            eval_err("1 / 0"),
+            EvaluateError::AttemptToDivideByZero(_)
+        ));
+    }
+
+    #[test]
+    fn test_failed_assignment_does_not_bind() {
+        let mut evaluation_env = Environment::new();
+        assert!(run("let a = b", &mut evaluation_env).is_err());
+        assert_eq!(evaluation_env.get("a"), None);
+    }
+
+    // every input must produce either a value or an error, never a panic
+    #[test]
+    fn test_no_input_panics() {
+        let inputs: Vec<&'static str> = vec![
+            "-x",
+            "--x",
+            "x = -y",
+            "let a = -b",
+            "x + 1",
+            "1 + x",
+            "x * y",
+            "(x) / 2",
+            "2 ^ x",
+            "-(a = 1)",
+            "(a = 1) + 1",
+            "1 + (a = 1)",
+            "1 = 2",
+            "1 / 0",
+            "1.0 / 0",
+            "",
+            "1 2",
+            "let",
+            "let 1",
+            "let a",
+            "let a =",
+            "1 +",
+            "+",
+            "-",
+            "*",
+            "(",
+            ")",
+            "()",
+            "(1 +",
+            "1 + 2)",
+            "((1)",
+            "1 + * 2",
+            "= 1",
+            "1 =",
+            "2147483647 + 1",
+            "(0 - 2147483647) - 2",
+            "65536 * 65536",
+            "-((0 - 2147483647) - 1)",
+            "((0 - 2147483647) - 1) / -1",
+            "2 ^ 31",
+            "2 ^ -1",
+            "0 ^ -1",
+            "2 ^ 2147483647",
+            "99999999999999999999 ^ 99999999999999999999",
+            "99999999999999999999 / 0",
+            "1²",
+            "!1",
+            "-true",
+            "true + 1",
+            "1 < true",
+            "1 == false",
+            "1 && true",
+            "true || 1",
+            "false && 1",
+            "!(a = 1)",
+            "(a = 1) == true",
+            "!",
+            "1 <",
+            "== 1",
+            "if",
+            "if 1 { 2 }",
+            "if true { a = 1 } + 1",
+            "if false { 1 } * 2",
+            "if true { } else { 1 }",
+            "else { 1 }",
+            "while 1 { }",
+            "while true { x }",
+            "{ 1; 2",
+            "(while true { })",
+            "{}",
+            ";",
+            "0..1.5",
+            "(0..2) + 1",
+            "-(0..2)",
+            "for i in 3 { }",
+            "for i in 0..3 { x }",
+            "for i in",
+            "for in 0..1 {}",
+            "0...1",
+            "..",
+            "1..",
+            "f()",
+            "1(2)",
+            "fn f(a) { a }; f()",
+            "fn f(a) { a }; f(1, 2)",
+            "fn f() { }; f() + 1",
+            "return 1",
+            "fn f() { return }; -f()",
+            "fn f(a, a) { a }; f(1, 2)",
+            "fn f() { f }; f()()()",
+            "(fn)",
+            "|x| x",
+            "(|x| x)()",
+            "(|| y)()",
+            "|| ||",
+        ];
+
+        for input in inputs {
+            let outcome = panic::catch_unwind(|| run(input, &mut Environment::new()));
+            assert!(outcome.is_ok(), "input {:?} panicked", input);
+        }
+    }
+
+    #[test]
+    fn test_booleans() {
+        let tests: Vec<(&'static str, bool)> = vec![
+            ("true", true),
+            ("!true", false),
+            ("!!true", true),
+            ("1 < 2", true),
+            ("2 <= 2", true),
+            ("1 > 2", false),
+            ("3 >= 2 + 1", true),
+            ("1 == 1.0", true),
+            ("7 / 2 == 3.5", true),
+            ("1 / 3 < 0.5", true),
+            ("99999999999999999999 > 2147483647", true),
+            ("1 != 2", true),
+            ("true == false", false),
+            ("true != false", true),
+            ("1 < 2 == 2 < 3", true),
+            ("true && false", false),
+            ("true || false && false", true),
+            ("!(1 > 2) && 2 > 1", true),
+            ("1.0 / 0 > 99999999999999999999", true),
+            ("0.0 / 0 == 0.0 / 0", false),
+            ("0.0 / 0 != 0.0 / 0", true),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, Value::Bool(expected), "evaluating {}", source);
+        }
+
+        let mut evaluation_env = Environment::new();
+        run("let a = 1 < 2", &mut evaluation_env).unwrap();
+        assert_eq!(evaluation_env.get("a"), Some(&Value::Bool(true)));
+        assert_eq!(evaluation_env.get("a").unwrap().to_string(), "true");
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() {
+        // the right side is never evaluated, so the undefined
+        // variable and the mismatched type are not reported
+        let tests: Vec<(&'static str, bool)> = vec![
+            ("false && x", false),
+            ("true || x", true),
+            ("false && 1", false),
+            ("true || 1", true),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, Value::Bool(expected), "evaluating {}", source);
+        }
+
+        let mut evaluation_env = Environment::new();
+        run("let a = 1", &mut evaluation_env).unwrap();
+        run("false && (a = 2) == 2", &mut evaluation_env).unwrap();
+        assert_eq!(evaluation_env.get("a"), Some(&Value::I32(1)));
+    }
+
+    #[test]
+    fn test_mixing_bools_and_numbers_is_an_error() {
+        let expects_number: Vec<&'static str> =
+            vec!["true + 1", "2 * false", "-true", "1 < true", "true ^ 2"];
+        for source in expects_number {
+            assert!(
+                matches!(eval_err(source), EvaluateError::ExpectedNumber("bool", _)),
+                "evaluating {}",
+                source
+            );
+        }
+
+        let expects_bool: Vec<&'static str> =
+            vec!["!1", "1 && true", "true && 1", "false || 1.5", "1 == true"];
+        for source in expects_bool {
+            assert!(
+                matches!(eval_err(source), EvaluateError::ExpectedBool(_, _)),
+                "evaluating {}",
+                source
+            );
+        }
+
+        assert!(matches!(
+            eval_err("let a = 0; !(a = 1)"),
+            EvaluateError::VariableDoesNotHaveAValue(_)
+        ));
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            ("if true { 1 } else { 2 }", Value::I32(1)),
+            ("if 1 > 2 { 1 } else { 2 }", Value::I32(2)),
+            ("if false { 1 }", Value::Unit),
+            (
+                "if false { 1 } else if 2 < 3 { 2 } else { 3 }",
+                Value::I32(2),
+            ),
+            ("10 * if true { 2 } else { 3 } + 1", Value::I32(21)),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        // only the chosen branch is evaluated
+        let mut evaluation_env = Environment::new();
+        run("let x = 0 - 3", &mut evaluation_env).unwrap();
+        run(
+            "let abs = if x < 0 { -x } else { undefined }",
+            &mut evaluation_env,
+        )
+        .unwrap();
+        assert_eq!(evaluation_env.get("abs"), Some(&Value::I32(3)));
+
+        assert!(matches!(
+            eval_err("if 1 { 2 }"),
+            EvaluateError::ExpectedBool("integer", _)
+        ));
+        assert!(matches!(
+            eval_err("if false { 1 } + 1"),
+            EvaluateError::VariableDoesNotHaveAValue(_)
+        ));
+    }
+
+    #[test]
+    fn test_blocks_and_while_loops() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            ("{ 1; 2 }", Value::I32(2)),
+            ("{}", Value::Unit),
+            ("1 + { 2; 3 }", Value::I32(4)),
+            ("let a = 1; a + 1", Value::I32(2)),
+            ("let a = 0; while a < 5 { a = a + 1 }", Value::Unit),
+            (
+                "let n = 10; let total = 0; while n > 0 { total = total + n; n = n - 1 }; total",
+                Value::I32(55),
+            ),
+            (
+                "let a = 1; let b = 0; while a < 100 { if a > 10 { b = b + 1 } else { b = b }; a = a * 2 }; b",
+                Value::I32(3),
+            ),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        // statements run against the same environment
+        let mut evaluation_env = Environment::new();
+        run("let i = 0; while i < 3 { i = i + 1 }", &mut evaluation_env).unwrap();
+        assert_eq!(evaluation_env.get("i"), Some(&Value::I32(3)));
+
+        assert!(matches!(
+            eval_err("while 1 { }"),
+            EvaluateError::ExpectedBool("integer", _)
+        ));
+        assert!(matches!(
+            eval_err("let a = 0; while a < 1 { a = a + b }"),
+            EvaluateError::ErrUninitializedVariable(name, _) if name == "b"
+        ));
+    }
+
+    #[test]
+    fn test_ranges_and_for_loops() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            (
+                "let total = 0; for i in 0..5 { total = total + i }; total",
+                Value::I32(10),
+            ),
+            (
+                "let total = 0; for i in 1..=5 { total = total + i }; total",
+                Value::I32(15),
+            ),
+            (
+                "let count = 0; for i in 3..3 { count = count + 1 }; count",
+                Value::I32(0),
+            ),
+            (
+                "let count = 0; for i in 3..=3 { count = count + 1 }; count",
+                Value::I32(1),
+            ),
+            (
+                "let count = 0; for i in 5..0 { count = count + 1 }; count",
+                Value::I32(0),
+            ),
+            (
+                "let last = 0; for i in 2147483646..=2147483648 { last = i }; last",
+                Value::BigInt("2147483648".parse().unwrap()),
+            ),
+            (
+                "let n = 3; let total = 0; for i in 0..n + 1 { for j in 0..i { total = total + 1 } }; total",
+                Value::I32(6),
+            ),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        assert_eq!(
+            run("0..=10", &mut Environment::new()).unwrap().to_string(),
+            "0..=10"
+        );
+
+        // the loop variable does not leak and restores the shadowed one
+        let mut evaluation_env = Environment::new();
+        run("for i in 0..3 { i }", &mut evaluation_env).unwrap();
+        assert_eq!(evaluation_env.get("i"), None);
+
+        run("let i = 100; for i in 0..3 { i }", &mut evaluation_env).unwrap();
+        assert_eq!(evaluation_env.get("i"), Some(&Value::I32(100)));
+
+        assert!(run("for i in 0..3 { i + x }", &mut evaluation_env).is_err());
+        assert_eq!(evaluation_env.get("i"), Some(&Value::I32(100)));
+
+        assert!(matches!(
+            eval_err("0..1.5"),
+            EvaluateError::ExpectedInteger("float", _)
+        ));
+        assert!(matches!(
+            eval_err("for i in 3 { }"),
+            EvaluateError::ExpectedRange("integer", _)
+        ));
+        assert!(matches!(
+            eval_err("(0..2) + 1"),
+            EvaluateError::ExpectedNumber("range", _)
+        ));
+    }
+
+    #[test]
+    fn test_functions() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            ("fn add(a, b) { a + b }; add(1, 2)", Value::I32(3)),
+            (
+                "fn fact(n) { if n < 2 { 1 } else { n * fact(n - 1) } }; fact(20)",
+                Value::BigInt("2432902008176640000".parse().unwrap()),
+            ),
+            (
+                "fn fib(n) { if n < 2 { return n }; fib(n - 1) + fib(n - 2) }; fib(15)",
+                Value::I32(610),
+            ),
+            // `return` leaves loops and nested blocks
+            (
+                "fn search(n) { for i in 0..n { if i * i > n { return i } }; 0 }; search(50)",
+                Value::I32(8),
+            ),
+            ("fn nothing() { return }; nothing()", Value::Unit),
+            ("fn one() { 1 }; one() + one()", Value::I32(2)),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        // functions are values too
+        let output = run("fn f() { f }; f()()()", &mut Environment::new()).unwrap();
+        assert_eq!(output.to_string(), "<fn f>");
+
+        // parameters do not leak and restore the variables they hide
+        let mut evaluation_env = Environment::new();
+        run("let a = 10; fn f(a, b) { a + b }", &mut evaluation_env).unwrap();
+        assert_eq!(run("f(1, 2)", &mut evaluation_env).unwrap(), Value::I32(3));
+        assert_eq!(evaluation_env.get("a"), Some(&Value::I32(10)));
+        assert_eq!(evaluation_env.get("b"), None);
+
+        assert!(matches!(
+            eval_err("fn f(a) { a }; f(1, 2)"),
+            EvaluateError::WrongNumberOfArguments {
+                expected: 1,
+                found: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            eval_err("let a = 1; a(2)"),
+            EvaluateError::ExpectedFunction("integer", _)
+        ));
+        assert!(matches!(
+            eval_err("fn f() { 1 }; f + 1"),
+            EvaluateError::ExpectedNumber("function", _)
+        ));
+        assert!(matches!(eval_err("return 1"), EvaluateError::Return(_, _)));
+    }
+
+    #[test]
+    fn test_type_errors_name_what_they_found() {
+        let tests: Vec<(&'static str, &'static str, Option<&'static str>)> = vec![
+            (
+                "true + 1",
+                "expected a number, found a bool",
+                Some("note: bools can only be used with `==`, `!=`, `&&`, `||` and `!`"),
+            ),
+            ("sqrt + 1", "expected a number, found a function", None),
+            ("(0..2) * 2", "expected a number, found a range", None),
+            (
+                "if 1 { 2 }",
+                "expected a bool, found an integer",
+                Some("help: use a comparison like `x != 0` to get a bool"),
+            ),
+            (
+                "for i in 3 { i }",
+                "expected a range, found an integer",
+                Some("help: loops look like `for i in 0..10 { ... }`"),
+            ),
+            (
+                "1..2.5",
+                "expected an integer, found a float",
+                Some("note: ranges can only be built from integers"),
+            ),
+        ];
+
+        for (source, message, note) in tests {
+            let diagnostic = run(source, &mut Environment::new()).unwrap_err();
+            assert_eq!(diagnostic.message, message, "evaluating {}", source);
+            assert_eq!(diagnostic.notes.first().map(String::as_str), note);
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            ("let n = 10; let add = |x| x + n; add(5)", Value::I32(15)),
+            ("(|a, b| a * b)(6, 7)", Value::I32(42)),
+            ("let one = || 1; one() + one()", Value::I32(2)),
+            // closures can be passed to functions
+            (
+                "fn twice(f, x) { f(f(x)) }; twice(|x| x * 3, 2)",
+                Value::I32(18),
+            ),
+            // and returned from them, keeping the parameters they use
+            (
+                "fn adder(n) { |x| x + n }; let addfive = adder(5); addfive(1)",
+                Value::I32(6),
+            ),
+            (
+                "let compose = |f, g| |x| f(g(x)); compose(|x| x + 1, |x| x * 2)(10)",
+                Value::I32(21),
+            ),
+            // captures are copies taken when the closure is created
+            ("let n = 1; let get = || n; n = 2; get()", Value::I32(1)),
+            // parameters win over captured variables with the same name
+            ("let x = 100; let id = |x| x + 0 * x; id(1)", Value::I32(1)),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        // calling a closure does not change the variables around it
+        let mut evaluation_env = Environment::new();
+        run("let n = 1; let set = || n = 5; set()", &mut evaluation_env).unwrap();
+        assert_eq!(evaluation_env.get("n"), Some(&Value::I32(1)));
+        assert_eq!(evaluation_env.get("x"), None);
+
+        assert_eq!(
+            run("|x| x", &mut Environment::new()).unwrap().to_string(),
+            "<closure>"
+        );
+        assert!(matches!(
+            eval_err("(|x| x)()"),
+            EvaluateError::WrongNumberOfArguments {
+                name: None,
+                expected: 1,
+                found: 0,
+                ..
+            }
+        ));
+        assert!(matches!(
+            eval_err("(|| y)()"),
+            EvaluateError::ErrUninitializedVariable(name, _) if name == "y"
+        ));
+    }
+
+    #[test]
+    fn test_scopes() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            // `let` in a block hides the outer variable until the block ends
+            ("let a = 1; { let a = 2 }; a", Value::I32(1)),
+            ("let a = 1; { let a = a + 1; a }", Value::I32(2)),
+            // while plain assignment changes it
+            ("let a = 1; { a = 2 }; a", Value::I32(2)),
+            ("let a = 1; if true { let a = 5; a = 6 }; a", Value::I32(1)),
+            (
+                "let total = 0; for i in 0..3 { let square = i * i; total = total + square }; total",
+                Value::I32(5),
+            ),
+            // functions can change global variables
+            (
+                "let count = 0; fn inc() { count = count + 1 }; inc(); inc(); count",
+                Value::I32(2),
+            ),
+            // and call themselves when they are defined inside other functions
+            (
+                "fn outer(n) { fn inner(k) { if k == 0 { 0 } else { 1 + inner(k - 1) } }; inner(n) }; outer(5)",
+                Value::I32(5),
+            ),
+            // nested functions see the variables of the function they are defined in
+            ("fn outer(n) { fn inner() { n }; inner() }; outer(5)", Value::I32(5)),
+            (
+                "fn outer(n) { let twice = n * 2; fn add(k) { k + twice }; fn apply() { add(n) }; apply() }; outer(3)",
+                Value::I32(9),
+            ),
+            // and functions defined inside blocks the variables of the block
+            ("{ let x = 1; fn f() { x }; f() }", Value::I32(1)),
+            (
+                "let counter = 0; for i in 1..=3 { fn add() { counter = counter + i }; add() }; counter",
+                Value::I32(6),
+            ),
+            // like closures, they keep a copy of the local variables
+            ("{ let x = 1; fn f() { x }; x = 2; f() }", Value::I32(1)),
+            (
+                "fn make(n) { fn get() { n }; get }; let get = make(7); get()",
+                Value::I32(7),
+            ),
+            // while the global ones are seen as they change
+            ("let g = 1; fn f() { g }; g = 2; f()", Value::I32(2)),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        let mut evaluation_env = Environment::new();
+        run("{ let inner = 1 }", &mut evaluation_env).unwrap();
+        assert_eq!(evaluation_env.get("inner"), None);
+
+        // functions don't see the local variables of their caller
+        assert!(matches!(
+            eval_err("fn f() { x }; { let x = 1; f() }"),
+            EvaluateError::ErrUninitializedVariable(name, _) if name == "x"
+        ));
+        assert!(matches!(
+            eval_err("fn f() { let y = 1 }; f(); y"),
+            EvaluateError::ErrUninitializedVariable(name, _) if name == "y"
+        ));
+    }
+
+    #[test]
+    fn test_declarations_and_constants() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            ("const a = 2; a * 3", Value::I32(6)),
+            // constants can be shadowed in inner scopes
+            ("const a = 1; { let a = 2; a = 3; a }", Value::I32(3)),
+            ("let a = 1; { let a = 2 }; a", Value::I32(1)),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        assert!(matches!(
+            eval_err("a = 1"),
+            EvaluateError::UndeclaredAssignment(name, _) if name == "a"
+        ));
+        assert!(matches!(
+            eval_err("{ let a = 1 }; a = 2"),
+            EvaluateError::UndeclaredAssignment(name, _) if name == "a"
+        ));
+        assert!(matches!(
+            eval_err("const a = 1; a = 2"),
+            EvaluateError::AssignToConstant(name, _) if name == "a"
+        ));
+        assert!(matches!(
+            eval_err("const a = 1; fn f() { a = 2 }; f()"),
+            EvaluateError::AssignToConstant(name, _) if name == "a"
+        ));
+        assert!(matches!(
+            eval_err("let a = 1; let a = 2"),
+            EvaluateError::Redeclaration(name, _) if name == "a"
+        ));
+        assert!(matches!(
+            eval_err("let a = 1; const a = 2"),
+            EvaluateError::Redeclaration(name, _) if name == "a"
+        ));
+        assert!(matches!(
+            eval_err("fn f() { 1 }; fn f() { 2 }"),
+            EvaluateError::Redeclaration(name, _) if name == "f"
+        ));
+
+        // the failed assignment leaves the constant untouched
+        let mut evaluation_env = Environment::new();
+        run("const a = 1", &mut evaluation_env).unwrap();
+        assert!(run("a = 2", &mut evaluation_env).is_err());
+        assert_eq!(evaluation_env.get("a"), Some(&Value::I32(1)));
+
+        let config = EvaluationConfig {
+            allow_redeclaration: true,
+            ..Default::default()
+        };
+        let output = run_with(
+            "let a = 1; let a = a + 1; a",
+            &mut Environment::new(),
+            &config,
+        );
+        assert_eq!(output.unwrap(), Value::I32(2));
+        assert!(matches!(
+            eval_err_with("const a = 1; a = 2", &config),
+            EvaluateError::AssignToConstant(_, _)
+        ));
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let config = EvaluationConfig {
+            max_call_depth: 20,
+            ..Default::default()
+        };
+
+        let output = run_with(
+            "fn fact(n) { if n < 2 { 1 } else { n * fact(n - 1) } }; fact(10)",
+            &mut Environment::new(),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(output, Value::I32(3628800));
+
+        let inputs: Vec<&'static str> = vec![
+            "fn f(n) { f(n + 1) }; f(0)",
+            "let f = |n| f(n); f(1) + 1",
+            "fn fact(n) { if n < 2 { 1 } else { n * fact(n - 1) } }; fact(30)",
+        ];
+
+        for input in inputs {
+            assert!(
+                matches!(
+                    eval_err_with(input, &config),
+                    EvaluateError::CallDepthExceeded(20, _)
+                ),
+                "evaluating {}",
+                input
+            );
+        }
+
+        // the frames of the failed calls are dropped
+        let mut evaluation_env = Environment::new();
+        assert!(run_with("fn f() { f() }; f()", &mut evaluation_env, &config).is_err());
+        assert_eq!(evaluation_env.call_depth(), 0);
+    }
+
+    #[test]
+    fn test_deep_recursion_does_not_overflow() {
+        // test threads have a small stack, the evaluator grows it when needed
+        let mut evaluation_env = Environment::new();
+        let output = run(
+            "fn depth(n) { if n == 0 { 0 } else { 1 + depth(n - 1) } }; depth(999)",
+            &mut evaluation_env,
+        );
+        assert_eq!(output.unwrap(), Value::I32(999));
+
+        assert!(matches!(
+            eval_err("fn f(n) { f(n + 1) }; f(0)"),
+            EvaluateError::CallDepthExceeded(1000, _)
+        ));
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let config = EvaluationConfig {
+            max_nesting_depth: 50,
+            ..Default::default()
+        };
+
+        let nested = |depth: usize| format!("{}1{}", "(-".repeat(depth), ")".repeat(depth));
+        assert_eq!(
+            run_with(&nested(20), &mut Environment::new(), &config).unwrap(),
+            Value::I32(1)
+        );
+        assert!(matches!(
+            eval_err_with(&nested(60), &config),
+            EvaluateError::NestingTooDeep(50, _)
+        ));
+
+        // the expressions of every running call are counted
+        let mut evaluation_env = Environment::new();
+        let recursion = "fn f(n) { if n == 0 { 0 } else { f(n - 1) } }; f(100)";
+        assert!(matches!(
+            eval_err_with(recursion, &config),
+            EvaluateError::NestingTooDeep(50, _)
+        ));
+
+        // and the count starts again after an error
+        assert!(run_with(recursion, &mut evaluation_env, &config).is_err());
+        assert_eq!(
+            run_with("f(5)", &mut evaluation_env, &config).unwrap(),
+            Value::I32(0)
+        );
+
+        // chains of operators are not nested, however long they are
+        let chain = format!("fn g() {{ 0{} }}; g()", " + 1".repeat(100_000));
+        assert_eq!(
+            run_with(&chain, &mut Environment::new(), &config).unwrap(),
+            Value::I32(100_000)
+        );
+        let chain = format!("false{} || true", " && x".repeat(100));
+        assert_eq!(
+            run_with(&chain, &mut Environment::new(), &config).unwrap(),
+            Value::Bool(true)
+        );
+    }
+
+    fn ratio(numerator: i32, denominator: i32) -> Value {
+        let value = Rational::new(BigInt::from(numerator), BigInt::from(denominator));
+        Value::Rational(value.unwrap())
+    }
+
+    #[test]
+    fn test_builtins() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            ("sqrt(16)", Value::F64(4.0)),
+            ("sqrt(2) * sqrt(2) > 1.99", Value::Bool(true)),
+            ("sin(0) + cos(0)", Value::F64(1.0)),
+            ("tan(0)", Value::F64(0.0)),
+            ("log(1)", Value::F64(0.0)),
+            ("abs(0 - 5)", Value::I32(5)),
+            ("abs(-5 / 2)", ratio(5, 2)),
+            ("abs(-2.5)", Value::F64(2.5)),
+            (
+                "abs(-2147483648)",
+                Value::BigInt("2147483648".parse().unwrap()),
+            ),
+            ("floor(7 / 2)", Value::I32(3)),
+            ("floor(-7 / 2)", Value::I32(-4)),
+            ("ceil(7 / 2)", Value::I32(4)),
+            ("round(7 / 2)", Value::I32(4)),
+            ("round(-2.5)", Value::F64(-3.0)),
+            (
+                "floor(99999999999999999999)",
+                Value::BigInt("99999999999999999999".parse().unwrap()),
+            ),
+            ("min(3, 1 / 2)", ratio(1, 2)),
+            ("max(3, 2.5)", Value::I32(3)),
+            ("max(min(10, 20), 5)", Value::I32(10)),
+            // builtins are values that can be passed around
+            ("fn apply(f, x) { f(x) }; apply(abs, 0 - 3)", Value::I32(3)),
+            // and variables with the same name hide them
+            ("let abs = 1; abs + 1", Value::I32(2)),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        assert_eq!(
+            run("sqrt", &mut Environment::new()).unwrap().to_string(),
+            "<builtin sqrt>"
+        );
+        // NaN wins whichever side it is on
+        for source in [
+            "min(1, sqrt(-1))",
+            "min(sqrt(-1), 1)",
+            "max(1, sqrt(-1))",
+            "max(sqrt(-1), 1)",
+        ] {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert!(
+                matches!(output, Value::F64(value) if value.is_nan()),
+                "evaluating {}",
+                source
+            );
+        }
+        assert!(matches!(
+            eval_err("sqrt(1, 2)"),
+            EvaluateError::WrongNumberOfArguments {
+                name: Some(name),
+                expected: 1,
+                found: 2,
+                ..
+            } if name == "sqrt"
+        ));
+        assert!(matches!(
+            eval_err("max(1)"),
+            EvaluateError::WrongNumberOfArguments {
+                expected: 2,
+                found: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            eval_err("sqrt(true)"),
+            EvaluateError::ExpectedNumber("bool", _)
+        ));
+        assert!(matches!(
+            eval_err("min(1, 0..2)"),
+            EvaluateError::ExpectedNumber("range", _)
+        ));
+        assert!(matches!(
+            eval_err("floor(sqrt)"),
+            EvaluateError::ExpectedNumber("function", _)
+        ));
+        assert!(matches!(
+            eval_err("let a = 0; abs(a = 1)"),
+            EvaluateError::VariableDoesNotHaveAValue(_)
+        ));
+        assert!(matches!(
+            eval_err("sqrt + 1"),
+            EvaluateError::ExpectedNumber("function", _)
+        ));
+    }
+
+    #[test]
+    fn test_prelude_constants() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            ("cos(pi)", Value::F64(-1.0)),
+            ("tau == 2 * pi", Value::Bool(true)),
+            ("log(e)", Value::F64(1.0)),
+            ("inf > 99999999999999999999", Value::Bool(true)),
+            ("nan == nan", Value::Bool(false)),
+            // they can still be shadowed in inner scopes
+            ("{ let pi = 3; pi }", Value::I32(3)),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::with_prelude()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        let mut evaluation_env = Environment::with_prelude();
+        assert_eq!(
+            run("pi = 3", &mut evaluation_env).unwrap_err().code,
+            "E0217"
+        );
+        assert_eq!(
+            evaluation_env.get("pi"),
+            Some(&Value::F64(std::f64::consts::PI))
+        );
+
+        // but a global `let` hides them like any outer variable
+        assert_eq!(
+            run("let e = 3; e = e + 1; e", &mut evaluation_env).unwrap(),
+            Value::I32(4)
+        );
+        assert_eq!(
+            run("let e = 5", &mut evaluation_env).unwrap_err().code,
+            "E0218"
+        );
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let inputs: Vec<&'static str> = vec![
+            "2147483647 + 1",
+            "(0 - 2147483647) - 2",
+            "65536 * 65536",
+            "-((0 - 2147483647) - 1)",
+            "((0 - 2147483647) - 1) / -1",
+            "2 ^ 31",
+        ];
+
+        let config = EvaluationConfig {
+            overflow: OverflowMode::Error,
+            division: DivisionMode::Truncate,
+            ..Default::default()
+        };
+
+        for input in inputs {
+            assert!(
+                matches!(
+                    eval_err_with(input, &config),
+                    EvaluateError::IntegerOverflow(_)
+                ),
+                "evaluating {}",
+                input
+            );
+        }
+
+        let float_division = EvaluationConfig {
+            division: DivisionMode::Float,
+            ..Default::default()
+        };
+
+        let output = run_with("2 ^ -1", &mut Environment::new(), &float_division).unwrap();
+        assert_eq!(output, Value::F64(0.5));
+    }
+
+    #[test]
+    fn test_integer_overflow_promotes_to_bigint() {
+        let tests: Vec<(&'static str, &'static str)> = vec![
+            ("2147483647 + 1", "2147483648"),
+            ("65536 * 65536", "4294967296"),
+            ("2 ^ 31", "2147483648"),
+            ("-((0 - 2147483647) - 1)", "2147483648"),
+            ("((0 - 2147483647) - 1) / -1", "2147483648"),
+            ("2 ^ 100", "1267650600228229401496703205376"),
+            ("99999999999999999999 + 1", "100000000000000000000"),
+            ("-99999999999999999999", "-99999999999999999999"),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert!(matches!(&output, Value::BigInt(_)), "evaluating {}", source);
+            assert_eq!(output.to_string(), expected, "evaluating {}", source);
+        }
+    }
+
+    #[test]
+    fn test_bigint_demotes_to_i32() {
+        let tests: Vec<(&'static str, Value)> = vec![
+            ("2147483648 - 1", Value::I32(i32::MAX)),
+            ("4294967296 / 65536", Value::I32(65536)),
+            ("(2 ^ 40) / (2 ^ 39)", Value::I32(2)),
+            ("-2147483648", Value::I32(i32::MIN)),
+            ("10000000000 * 0.5", Value::F64(5000000000.0)),
+            // exponents too big for an i32 only work with these bases
+            ("1 ^ 99999999999", Value::I32(1)),
+            ("1 ^ -99999999999", Value::I32(1)),
+            ("(0 - 1) ^ 99999999999", Value::I32(-1)),
+            ("(0 - 1) ^ 100000000000", Value::I32(1)),
+            ("0 ^ 99999999999", Value::I32(0)),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, expected, "evaluating {}", source);
+        }
+
+        assert!(matches!(
+            eval_err("10000000000 / 0"),
+            EvaluateError::AttemptToDivideByZero(_)
+        ));
+        assert!(matches!(
+            eval_err("2 ^ 1000000"),
+            EvaluateError::IntegerOverflow(_)
+        ));
+        assert!(matches!(
+            eval_err("2 ^ 99999999999"),
+            EvaluateError::IntegerOverflow(_)
+        ));
+    }
+
+    #[test]
+    fn test_rational_division() {
+        let tests: Vec<(&'static str, &'static str)> = vec![
+            ("7 / 2", "7/2"),
+            ("-7 / 2", "-7/2"),
+            ("1 / 3 + 1 / 6", "1/2"),
+            ("(7 / 2) * 2", "7"),
+            ("(2 / 3) ^ 2", "4/9"),
+            ("(2 / 3) ^ -2", "9/4"),
+            ("2 ^ -2", "1/4"),
+            ("1 / 99999999999999999999", "1/99999999999999999999"),
+            ("(1 / 2) * 3.0", "1.5"),
+        ];
+
+        for (source, expected) in tests {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output.to_string(), expected, "evaluating {}", source);
+        }
+
+        assert_eq!(
+            run("6 / 3", &mut Environment::new()).unwrap(),
+            Value::I32(2)
+        );
+        assert!(matches!(
+            eval_err("(1 / 2) / 0"),
+            EvaluateError::AttemptToDivideByZero(_)
+        ));
+        assert!(matches!(
+            eval_err("0 ^ -1"),
+            EvaluateError::AttemptToDivideByZero(_)
+        ));
+    }
+
+    #[test]
+    fn test_division_modes() {
+        let tests: Vec<(DivisionMode, Value)> = vec![
+            (DivisionMode::Truncate, Value::I32(3)),
+            (DivisionMode::Float, Value::F64(3.5)),
+        ];
+
+        for (division, expected) in tests {
+            let config = EvaluationConfig {
+                division,
+                ..Default::default()
+            };
+
+            let output = run_with("7 / 2", &mut Environment::new(), &config).unwrap();
+            assert_eq!(output, expected, "dividing with {:?}", division);
+        }
+
+        let output = run("7 / 2", &mut Environment::new()).unwrap();
+        assert!(matches!(output, Value::Rational(_)));
+    }
+
+    #[test]
+    fn test_f64_precision() {
+        let output = run("0.1 + 0.2", &mut Environment::new()).unwrap();
+        assert_eq!(output, Value::F64(0.1 + 0.2));
+        assert_eq!(output.to_string(), "0.30000000000000004");
+
+        let output = run("16777217.0 + 1", &mut Environment::new()).unwrap();
+        assert_eq!(output.to_string(), "16777218");
+    }
+
+    #[test]
+    fn test_statements_without_value_produce_unit() {
+        let inputs: Vec<&'static str> = vec![
+            "",
+            "let a = 1",
+            "let a = 1; a = 2",
+            "if false { 1 }",
+            "while false { }",
+            "fn f() { }",
+        ];
+
+        for source in inputs {
+            let output = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(output, Value::Unit, "evaluating {}", source);
+        }
+
+        assert_eq!(Value::Unit.to_string(), "()");
+
+        #[allow(deprecated)]
+        let renamed: super::NumericObject = Value::I32(1);
+        assert_eq!(renamed, Value::I32(1));
+    }
+
+    #[test]
+    fn test_values_to_source() {
+        let values = [
+            ("-7", "(-7)"),
+            ("2 ^ 70", "1180591620717411303424"),
+            ("-1 / 3", "(-1/3)"),
+            ("2.0 * 3.0", "6.0"),
+            ("-0.1", "(-0.1)"),
+            ("1.0 / 0.0", "(1.0 / 0.0)"),
+            ("-1.0 / 0.0", "(-1.0 / 0.0)"),
+            ("true", "true"),
+            ("-2..=3", "((-2)..=3)"),
+            ("while false {}", "{}"),
+            ("sqrt", "sqrt"),
+        ];
+
+        for (source, expected) in values {
+            let value = run(source, &mut Environment::new()).unwrap();
+            assert_eq!(value.to_source().as_deref(), Some(expected), "{}", source);
+            assert_eq!(run(expected, &mut Environment::new()).unwrap(), value);
+        }
+
+        let nan = run("0.0 / 0.0", &mut Environment::new()).unwrap();
+        let restored = run(&nan.to_source().unwrap(), &mut Environment::new()).unwrap();
+        assert!(matches!(restored, Value::F64(value) if value.is_nan()));
+
+        // functions keep working when evaluated again, with their captures
+        let sources = [
+            "fn fact(n) { if n < 2 { 1 } else { n * fact(n - 1) } }; fact",
+            "let n = 10; let inc = |x| x + n; |f| f(inc(1))",
+        ];
+
+        let fact = run(sources[0], &mut Environment::new()).unwrap();
+        let fact = format!("{}(5)", fact.to_source().unwrap());
+        assert_eq!(
+            run(&fact, &mut Environment::new()).unwrap(),
+            Value::I32(120)
+        );
+
+        let nested = run(
+            "fn make(n) { fn get() { n }; get }; make(7)",
+            &mut Environment::new(),
+        );
+        let nested = nested.unwrap().to_source().unwrap();
+        assert_eq!(nested, "{ let n = 7; fn get() { n }; get }");
+        let nested = format!("{}()", nested);
+        assert_eq!(
+            run(&nested, &mut Environment::new()).unwrap(),
+            Value::I32(7)
+        );
+
+        let closure = run(sources[1], &mut Environment::new()).unwrap();
+        let closure = format!("{}(|x| x * 2)", closure.to_source().unwrap());
+        assert_eq!(
+            run(&closure, &mut Environment::new()).unwrap(),
+            Value::I32(22)
+        );
+    }
+
+    #[test]
+    fn test_display_significant_digits() {
+        let tests: Vec<(f64, usize, &'static str)> = vec![
+            (0.1 + 0.2, 15, "0.3"),
+            (1.23456, 3, "1.23"),
+            (2.5, 10, "2.5"),
+            (1234.5678, 6, "1234.57"),
+            (1234.5678, 2, "1.2e3"),
+            (0.000012345, 3, "1.23e-5"),
+            (0.00012345, 3, "0.000123"),
+            (-2.0 / 3.0, 4, "-0.6667"),
+            (100.0, 1, "1e2"),
+            (f64::INFINITY, 3, "inf"),
+            (0.0, 3, "0"),
+        ];
+
+        for (value, digits, expected) in tests {
+            let output = format!("{:.*}", digits, Value::F64(value));
+            assert_eq!(
+                output, expected,
+                "formatting {} with {} digits",
+                value, digits
+            );
+        }
+
+        assert_eq!(format!("{:.2}", Value::I32(12345)), "12345");
+    }
+}
@@ -0,0 +1,367 @@
+use std::rc::Rc;
+
+use crate::diagnostic::Diagnostic;
+use crate::environment::Environment;
+use crate::eval::{DivisionMode, EvaluationConfig, NativeFunction, Value};
+
+/// Runs alang programs keeping their variables between runs, it is what
+/// the REPL uses and what other programs can embed to evaluate expressions
+///
+/// ```
+/// use alang::{EvaluationConfig, Interpreter, Value};
+///
+/// let mut interpreter = Interpreter::new(EvaluationConfig::default());
+/// interpreter.register_fn("tax", 1, |arguments| match &arguments[0] {
+///     Value::I32(value) => Ok(Value::I32(value / 5)),
+///     _ => Err("expected an integer".to_string()),
+/// });
+/// assert_eq!(interpreter.evaluate("tax(100)"), Ok(Value::I32(20)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    environment: Environment,
+    config: EvaluationConfig,
+    /// the constants added with `register_fn` and `define_constant`,
+    /// declared again whenever the environment is rebuilt
+    host: Vec<(String, Value)>,
+}
+
+impl Interpreter {
+    /// an interpreter whose environment only has the prelude constants
+    pub fn new(config: EvaluationConfig) -> Interpreter {
+        Interpreter {
+            environment: Environment::with_prelude(),
+            config,
+            host: vec![],
+        }
+    }
+
+    /// the prelude together with the constants the host added
+    fn fresh_environment(&self) -> Environment {
+        let mut environment = Environment::with_prelude();
+        for (name, value) in &self.host {
+            environment.define_constant(name, value.clone());
+        }
+
+        environment
+    }
+
+    pub fn config(&self) -> &EvaluationConfig {
+        &self.config
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    /// exposes `function` to programs as a global called `name`, calls with
+    /// other than `arity` arguments fail before reaching it and the messages
+    /// it returns as errors are reported to the user
+    pub fn register_fn(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        let native = NativeFunction::new(name, arity, function);
+        self.define_constant(name, Value::Native(Rc::new(native)));
+    }
+
+    /// declares a constant called `name` that, like the functions added with
+    /// `register_fn`, outlives `reset` and `load`
+    pub fn define_constant(&mut self, name: &str, value: Value) {
+        self.host.retain(|(host_name, _)| host_name != name);
+        self.host.push((name.to_string(), value.clone()));
+        self.environment.define_constant(name, value);
+    }
+
+    /// whether `name` still holds the value the host gave it
+    fn is_host(&self, name: &str, value: &Value) -> bool {
+        self.host
+            .iter()
+            .any(|(host_name, host_value)| host_name == name && host_value == value)
+    }
+
+    /// forgets the global variable called `name`, returning whether there was one
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.environment.remove_global(name)
+    }
+
+    /// forgets every variable, leaving only the prelude constants
+    /// and the ones added with `register_fn` or `define_constant`
+    pub fn reset(&mut self) {
+        self.environment = self.fresh_environment();
+    }
+
+    /// writes the global variables as a program declaring them again, one per
+    /// line, which `load` restores. The prelude constants and the ones of the
+    /// host that were not changed are left out, and so are the variables that
+    /// can't be written down, like the closures using a function added with
+    /// `register_fn`, whose names are returned with the program
+    pub fn save(&self) -> (String, Vec<String>) {
+        let mut program = String::new();
+        let mut skipped = vec![];
+
+        for (name, value, constant) in self.environment.globals() {
+            if self.environment.is_prelude(name) || (constant && self.is_host(name, value)) {
+                continue;
+            }
+
+            let declaration = match value {
+                Value::Function(function)
+                    if !constant
+                        && function.name.as_deref() == Some(name)
+                        && function.captures.is_empty() =>
+                {
+                    let parameters = function.parameters.join(", ");
+                    format!("fn {}({}) {}", name, parameters, function.body.to_source())
+                }
+                value => {
+                    let Some(source) = value.to_source() else {
+                        skipped.push(name.to_string());
+                        continue;
+                    };
+                    let keyword = if constant { "const" } else { "let" };
+                    format!("{} {} = {}", keyword, name, source)
+                }
+            };
+
+            program.push_str(&declaration);
+            program.push('\n');
+        }
+
+        (program, skipped)
+    }
+
+    /// replaces every variable with the ones declared by `program`, usually
+    /// written by `save`, keeping the current ones when it fails. Like
+    /// `reset`, it keeps the constants added with `register_fn`
+    pub fn load(&mut self, program: &str) -> Result<(), Diagnostic> {
+        let mut environment = self.fresh_environment();
+        // fractions are saved as divisions so they must stay exact
+        let config = EvaluationConfig {
+            division: DivisionMode::Rational,
+            ..self.config
+        };
+
+        crate::evaluate(program, &mut environment, &config)?;
+        self.environment = environment;
+        Ok(())
+    }
+
+    /// runs `source` producing the value of its last statement
+    pub fn evaluate(&mut self, source: &str) -> Result<Value, Diagnostic> {
+        crate::evaluate(source, &mut self.environment, &self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::Interpreter;
+    use crate::eval::{DivisionMode, EvaluationConfig, Value};
+    use crate::lexer::token::Span;
+
+    fn interpreter() -> Interpreter {
+        Interpreter::new(EvaluationConfig::default())
+    }
+
+    #[test]
+    fn test_variables_persist_between_runs() {
+        let mut interpreter = interpreter();
+        interpreter.evaluate("let a = 2").unwrap();
+        interpreter.evaluate("fn double(x) { x * a }").unwrap();
+
+        assert_eq!(interpreter.evaluate("double(21)").unwrap(), Value::I32(42));
+        assert_eq!(interpreter.evaluate("").unwrap(), Value::Unit);
+        assert_eq!(interpreter.environment().get("a"), Some(&Value::I32(2)));
+
+        assert!(interpreter.remove("a"));
+        assert_eq!(interpreter.evaluate("a").unwrap_err().code, "E0206");
+
+        interpreter.define_constant("base", Value::I32(7));
+        interpreter.reset();
+        assert_eq!(interpreter.evaluate("double").unwrap_err().code, "E0206");
+        assert!(interpreter.environment().get("pi").is_some());
+        // the constants of the host are kept
+        assert_eq!(interpreter.evaluate("base").unwrap(), Value::I32(7));
+    }
+
+    #[test]
+    fn test_errors_of_earlier_functions_point_to_the_call() {
+        let mut interpreter = interpreter();
+        interpreter.evaluate("let f = || yy").unwrap();
+        interpreter
+            .evaluate("fn g(x) { let a = x; f() + a }")
+            .unwrap();
+
+        let source = "let ééééééé = g(1)";
+        let diagnostic = interpreter.evaluate(source).unwrap_err();
+        assert_eq!(diagnostic.code, "E0206");
+        assert_eq!(diagnostic.span, Some(Span::new(21, 25, 1, 15)));
+        assert_eq!(
+            diagnostic.notes.last().unwrap(),
+            "note: raised inside `g`, defined in an earlier input"
+        );
+        assert!(diagnostic
+            .render(source)
+            .contains("1 | let ééééééé = g(1)\n  |               ^^^^\n"));
+
+        // functions of the same input still point inside their body
+        let diagnostic = interpreter.evaluate("fn h() { zz }; h()").unwrap_err();
+        assert_eq!(diagnostic.span, Some(Span::new(9, 11, 1, 10)));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut original = interpreter();
+        original
+            .evaluate("let rate = 1 / 8; const limit = 2 ^ 40; let scale = |x| x * rate")
+            .unwrap();
+        original
+            .evaluate("fn fact(n) { if n < 2 { return 1 }; n * fact(n - 1) }; let factorial = fact")
+            .unwrap();
+        // variables hiding a prelude constant are saved
+        original.evaluate("let pi = 3").unwrap();
+        original.register_fn("tax", 1, |arguments| Ok(arguments[0].clone()));
+        original.define_constant("base", Value::I32(4));
+        original
+            .evaluate("let taxed = |x| tax(x) + 1; fn untaxed(x) { tax(x) - base }")
+            .unwrap();
+
+        let (saved, skipped) = original.save();
+        // closures holding a function only rust knows about can't be written down
+        assert_eq!(skipped, vec!["taxed"]);
+        assert_eq!(
+            saved.lines().collect::<Vec<_>>(),
+            vec![
+                "fn fact(n) { (if (n < 2) { return 1 }); (n * fact((n - 1))) }",
+                "let factorial = { fn fact(n) { (if (n < 2) { return 1 }); (n * fact((n - 1))) }; fact }",
+                "const limit = 1099511627776",
+                "let pi = 3",
+                "let rate = (1/8)",
+                "let scale = { let rate = (1/8); (|x| (x * rate)) }",
+                "fn untaxed(x) { (tax(x) - base) }",
+            ]
+        );
+
+        // the fractions stay exact whatever the division mode
+        let mut restored = Interpreter::new(EvaluationConfig {
+            division: DivisionMode::Truncate,
+            ..Default::default()
+        });
+        restored.register_fn("tax", 1, |arguments| Ok(arguments[0].clone()));
+        restored.define_constant("base", Value::I32(4));
+        restored.evaluate("let rate = 0").unwrap();
+        restored.load(&saved).unwrap();
+        assert_eq!(restored.save(), (saved, vec![]));
+        assert_eq!(
+            restored
+                .evaluate("factorial(5) + scale(16) + pi + untaxed(6)")
+                .unwrap(),
+            Value::I32(127)
+        );
+        assert_eq!(restored.evaluate("limit = 1").unwrap_err().code, "E0217");
+
+        // nothing changes when the program fails
+        assert_eq!(restored.load("let a = 1; b").unwrap_err().code, "E0206");
+        assert_eq!(restored.evaluate("a").unwrap_err().code, "E0206");
+        assert_eq!(
+            restored.evaluate("rate").unwrap(),
+            original.evaluate("rate").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deep_recursion_on_any_thread() {
+        // spawned threads have a 2 MiB stack, far less than the deepest
+        // calls allowed need, the interpreter grows it instead of crashing
+        let recursion = std::thread::spawn(|| {
+            let mut interpreter = interpreter();
+            interpreter
+                .evaluate("fn depth(n) { if n == 0 { 0 } else { 1 + depth(n - 1) } }")
+                .unwrap();
+
+            // values can't leave the thread, so only their text does
+            let mut evaluate = |source: &str| match interpreter.evaluate(source) {
+                Ok(value) => Ok(value.to_string()),
+                Err(err) => Err(err.code),
+            };
+
+            let deepest = evaluate("depth(999)");
+            let too_deep = evaluate("depth(5000)");
+            let too_nested = evaluate(&format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000)));
+
+            (deepest, too_deep, too_nested)
+        });
+
+        let (deepest, too_deep, too_nested) = recursion.join().unwrap();
+        assert_eq!(deepest, Ok("999".to_string()));
+        assert_eq!(too_deep, Err("E0215"));
+        assert_eq!(too_nested, Err("E0112"));
+    }
+
+    #[test]
+    fn test_register_fn() {
+        let mut interpreter = interpreter();
+        interpreter.register_fn("tax", 1, |arguments| match &arguments[0] {
+            Value::I32(value) => Ok(Value::I32(value / 5)),
+            other => Err(format!("expected an integer, found {}", other)),
+        });
+
+        assert_eq!(
+            interpreter.evaluate("tax(100) + 1").unwrap(),
+            Value::I32(21)
+        );
+        // it is a value like any other function
+        assert_eq!(
+            interpreter
+                .evaluate("fn apply(f) { f(50) }; apply(tax)")
+                .unwrap(),
+            Value::I32(10)
+        );
+        assert_eq!(
+            interpreter.evaluate("tax").unwrap().to_string(),
+            "<native tax>"
+        );
+
+        let wrong_arity = interpreter.evaluate("tax(1, 2)").unwrap_err();
+        assert_eq!(wrong_arity.code, "E0213");
+        assert_eq!(
+            wrong_arity.message,
+            "function `tax` takes 1 argument(s) but 2 were given"
+        );
+
+        let failed = interpreter.evaluate("tax(true)").unwrap_err();
+        assert_eq!(failed.code, "E0219");
+        assert_eq!(
+            failed.message,
+            "`tax` failed: expected an integer, found true"
+        );
+
+        assert_eq!(interpreter.evaluate("tax = 1").unwrap_err().code, "E0217");
+    }
+
+    #[test]
+    fn test_registered_closures_keep_their_state() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+
+        let mut interpreter = interpreter();
+        interpreter.register_fn("tick", 0, move |_| {
+            counter.set(counter.get() + 1);
+            Ok(Value::I32(counter.get()))
+        });
+        interpreter.define_constant("limit", Value::I32(3));
+
+        assert_eq!(
+            interpreter
+                .evaluate("let last = 0; while last < limit { last = tick() }; last")
+                .unwrap(),
+            Value::I32(3)
+        );
+        assert_eq!(calls.get(), 3);
+    }
+}
@@ -1,28 +1,113 @@
 pub mod token;
 
-use core::num;
 use std::{fmt, iter::Peekable, str::Chars};
-use token::Token;
+use token::{Span, SpannedToken, Token};
 
-#[derive(Debug, Clone)]
+use crate::bigint::BigInt;
+use crate::diagnostic::{Diagnostic, ErrorKind};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum LexerError {
-    InvalidInputChar(String),
+    InvalidInputChar(String, Span),
 }
 
 impl fmt::Display for LexerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LexerError::InvalidInputChar(c) => write!(f, "invalid input char: {}", c),
+            LexerError::InvalidInputChar(c, span) => {
+                write!(f, "invalid input char: {} at {}", c, span)
+            }
         }
     }
 }
 
-fn read_numeric(current: char, source: &mut Peekable<Chars>) -> String {
+impl From<LexerError> for Diagnostic {
+    fn from(err: LexerError) -> Diagnostic {
+        let kind = ErrorKind::Lexer(err.clone());
+        let diagnostic = match err {
+            LexerError::InvalidInputChar(c, span) => {
+                Diagnostic::new("E0001", format!("invalid input char `{}`", c))
+                    .with_span(span)
+                    .with_note(
+                        "help: only numbers, identifiers, booleans and operators are supported",
+                    )
+            }
+        };
+
+        diagnostic.with_kind(kind)
+    }
+}
+
+/// Char iterator that keeps track of where in the input
+/// the next char is, so every token can carry its span
+struct Source<'a> {
+    chars: Peekable<Chars<'a>>,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Source<'a> {
+    fn new(input: &'a str) -> Source<'a> {
+        Source {
+            chars: input.chars().peekable(),
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+
+    /// the char after the one returned by `peek`
+    fn peek_second(&self) -> Option<char> {
+        self.chars.clone().nth(1)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let current = self.chars.next()?;
+        self.offset += current.len_utf8();
+
+        if current == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(current)
+    }
+
+    /// consumes the next char only when it is `expected`
+    fn next_if_eq(&mut self, expected: char) -> bool {
+        match self.peek() {
+            Some(current) if *current == expected => self.next().is_some(),
+            _ => false,
+        }
+    }
+
+    /// an empty span at the position of the next char
+    fn mark(&self) -> Span {
+        Span::new(self.offset, self.offset, self.line, self.column)
+    }
+
+    /// extends a previous mark until the current position
+    fn span_since(&self, mark: Span) -> Span {
+        Span {
+            end: self.offset,
+            ..mark
+        }
+    }
+}
+
+fn read_numeric(current: char, source: &mut Source) -> String {
     let mut numbers_in_seq = vec![current.to_string()];
 
-    let while_numbers_push = |sequence: &mut Vec<String>, source: &mut Peekable<Chars>| {
+    let while_numbers_push = |sequence: &mut Vec<String>, source: &mut Source| {
         while let Some(current) = source.peek() {
-            if !current.is_numeric() {
+            if !current.is_ascii_digit() {
                 break;
             }
 
@@ -33,21 +118,34 @@ fn read_numeric(current: char, source: &mut Peekable<Chars>) -> String {
 
     while_numbers_push(&mut numbers_in_seq, source);
 
-    if let Some(current) = source.peek() {
-        match current {
-            '.' => {
-                // adds the '.' to the sequence
-                numbers_in_seq.push(source.next().unwrap().to_string());
-                while_numbers_push(&mut numbers_in_seq, source);
-            }
-            _ => {}
-        }
+    // `1..2` is a range, not the float `1.` followed by `.2`
+    if source.peek() == Some(&'.') && source.peek_second() != Some('.') {
+        // adds the '.' to the sequence
+        numbers_in_seq.push(source.next().unwrap().to_string());
+        while_numbers_push(&mut numbers_in_seq, source);
     }
 
     numbers_in_seq.join("")
 }
 
-fn read_keyword_or_identifier(current: char, source: &mut Peekable<Chars>) -> Option<String> {
+// the sequence only has ascii digits and at most one '.', so parsing can't fail
+fn numeric_token(numeric_sequence: &str) -> Token {
+    if numeric_sequence.contains('.') {
+        return Token::F64(numeric_sequence.parse::<f64>().unwrap_or(f64::NAN));
+    }
+
+    match numeric_sequence.parse::<i32>() {
+        Ok(value) => Token::I32(value),
+        // too large to fit in an i32
+        Err(_) => Token::BigInt(numeric_sequence.parse::<BigInt>().unwrap_or_default()),
+    }
+}
+
+fn read_keyword_or_identifier(current: char, source: &mut Source) -> Option<String> {
+    if !current.is_alphabetic() {
+        return None;
+    }
+
     let mut sequence = vec![current];
     while let Some(current) = source.peek() {
         if !current.is_alphabetic() {
@@ -60,50 +158,161 @@ fn read_keyword_or_identifier(current: char, source: &mut Peekable<Chars>) -> Op
     Some(sequence.iter().collect())
 }
 
-pub fn extract_token_stream(line: String) -> Result<Vec<Token>, LexerError> {
-    let mut source_as_chars = line.chars().peekable();
-
-    let mut tokens: Vec<Token> = vec![];
-
-    while let Some(current) = source_as_chars.next() {
-        match current {
-            ' ' | '\n' | '\r' => continue,
-            '+' => tokens.push(Token::Plus),
-            '-' => tokens.push(Token::Minus),
-            '*' => tokens.push(Token::Star),
-            '/' => tokens.push(Token::Slash),
-            '(' => tokens.push(Token::OpenParen),
-            ')' => tokens.push(Token::CloseParen),
-            '^' => tokens.push(Token::Caret),
-            '=' => tokens.push(Token::Assign),
+// tokens that can finish a statement when they are the last of a line
+fn ends_statement(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Ident(_)
+            | Token::F64(_)
+            | Token::I32(_)
+            | Token::BigInt(_)
+            | Token::True
+            | Token::False
+            | Token::CloseParen
+            | Token::CloseBrace
+            | Token::Return
+    )
+}
+
+// tokens that carry on the statement of the previous line when they start
+// a line, `-` is not one of them since it can start a statement as well
+fn continues_statement(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Plus
+            | Token::Star
+            | Token::Slash
+            | Token::Caret
+            | Token::Assign
+            | Token::Equal
+            | Token::NotEqual
+            | Token::Less
+            | Token::LessEqual
+            | Token::Greater
+            | Token::GreaterEqual
+            | Token::And
+            | Token::Or
+            | Token::DotDot
+            | Token::DotDotEq
+            | Token::Else
+    )
+}
+
+/// Adds a `;` at the end of every line that finishes a statement, so
+/// programs spanning many lines don't need them. Lines inside parentheses
+/// never finish a statement, so call arguments can be split across lines
+fn separate_lines(tokens: Vec<SpannedToken>) -> Vec<SpannedToken> {
+    let mut separated: Vec<SpannedToken> = Vec::with_capacity(tokens.len());
+    let mut open_delimiters: Vec<Token> = vec![];
+
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(current) = tokens.next() {
+        match current.token {
+            Token::OpenParen | Token::OpenBrace => open_delimiters.push(current.token.clone()),
+            Token::CloseParen | Token::CloseBrace => {
+                open_delimiters.pop();
+            }
+            _ => {}
+        }
+
+        let ends_line = match tokens.peek() {
+            Some(next) => next.span.line > current.span.line && !continues_statement(&next.token),
+            None => false,
+        };
+        let inside_parens = open_delimiters.last() == Some(&Token::OpenParen);
+
+        let separator = match ends_line && !inside_parens && ends_statement(&current.token) {
+            true => Some(SpannedToken::new(Token::Semicolon, current.span.after())),
+            false => None,
+        };
+
+        separated.push(current);
+        separated.extend(separator);
+    }
+
+    separated
+}
+
+pub fn extract_token_stream(line: String) -> Result<Vec<SpannedToken>, LexerError> {
+    let mut source = Source::new(&line);
+
+    let mut tokens: Vec<SpannedToken> = vec![];
+
+    loop {
+        let mark = source.mark();
+        let Some(current) = source.next() else {
+            break;
+        };
+
+        let token = match current {
+            ' ' | '\t' | '\n' | '\r' => continue,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '{' => Token::OpenBrace,
+            '}' => Token::CloseBrace,
+            ';' => Token::Semicolon,
+            ',' => Token::Comma,
+            '.' if source.next_if_eq('.') => match source.next_if_eq('=') {
+                true => Token::DotDotEq,
+                false => Token::DotDot,
+            },
+            '^' => Token::Caret,
+            '=' if source.next_if_eq('=') => Token::Equal,
+            '=' => Token::Assign,
+            '!' if source.next_if_eq('=') => Token::NotEqual,
+            '!' => Token::Bang,
+            '<' if source.next_if_eq('=') => Token::LessEqual,
+            '<' => Token::Less,
+            '>' if source.next_if_eq('=') => Token::GreaterEqual,
+            '>' => Token::Greater,
+            '&' if source.next_if_eq('&') => Token::And,
+            '|' if source.next_if_eq('|') => Token::Or,
+            '|' => Token::Pipe,
             _ => {
-                if current.is_numeric() {
-                    let numeric_sequence = read_numeric(current, &mut source_as_chars);
-                    match numeric_sequence.contains(".") {
-                        true => tokens.push(Token::F32(numeric_sequence.parse::<f32>().unwrap())),
-                        false => tokens.push(Token::I32(numeric_sequence.parse::<i32>().unwrap())),
+                if current.is_ascii_digit() {
+                    let numeric_sequence = read_numeric(current, &mut source);
+                    numeric_token(&numeric_sequence)
+                } else {
+                    match read_keyword_or_identifier(current, &mut source) {
+                        Some(value) => match value.as_ref() {
+                            "let" => Token::Let,
+                            "const" => Token::Const,
+                            "true" => Token::True,
+                            "false" => Token::False,
+                            "if" => Token::If,
+                            "else" => Token::Else,
+                            "while" => Token::While,
+                            "for" => Token::For,
+                            "in" => Token::In,
+                            "fn" => Token::Fn,
+                            "return" => Token::Return,
+                            _ => Token::Ident(value.clone()),
+                        },
+                        None => {
+                            return Err(LexerError::InvalidInputChar(
+                                current.to_string(),
+                                source.span_since(mark),
+                            ))
+                        }
                     }
-                    continue;
-                }
-
-                match read_keyword_or_identifier(current, &mut source_as_chars) {
-                    Some(value) => match value.as_ref() {
-                        "let" => tokens.push(Token::Let),
-                        _ => tokens.push(Token::Ident(value.clone())),
-                    },
-                    None => return Err(LexerError::InvalidInputChar(current.to_string())),
                 }
             }
         };
+
+        tokens.push(SpannedToken::new(token, source.span_since(mark)));
     }
 
-    Ok(tokens)
+    Ok(separate_lines(tokens))
 }
 
 #[cfg(test)]
 mod tests {
     #[allow(unused_imports)]
-    use super::{extract_token_stream, Token};
+    use super::{extract_token_stream, LexerError, Span, Token};
 
     #[test]
     fn test_extract_token_stream() {
@@ -118,6 +327,16 @@ mod tests {
             "let",
             "a",
             "let a = 1",
+            "2147483648",
+            "true && !false",
+            "1 == 2 != 3 < 4 <= 5 > 6 >= 7 || a = 1",
+            "if a { 1 } else { 2 }",
+            "while a { a; }",
+            "for i in 0..10 { 1..=2 }",
+            "1.5..2.",
+            "fn f(a, b) { return a }",
+            "|x, y| x || y",
+            "const half = 0.5",
         ];
         let expectations: Vec<Vec<Token>> = vec![
             vec![Token::I32(1), Token::Plus, Token::I32(1)],
@@ -144,7 +363,7 @@ mod tests {
                 Token::CloseParen,
             ],
             vec![Token::I32(2), Token::Caret, Token::I32(2)],
-            vec![Token::F32(10.0)],
+            vec![Token::F64(10.0)],
             vec![Token::Let],
             vec![Token::Ident("a".into())],
             vec![
@@ -153,14 +372,180 @@ mod tests {
                 Token::Assign,
                 Token::I32(1),
             ],
+            vec![Token::BigInt("2147483648".parse().unwrap())],
+            vec![Token::True, Token::And, Token::Bang, Token::False],
+            vec![
+                Token::I32(1),
+                Token::Equal,
+                Token::I32(2),
+                Token::NotEqual,
+                Token::I32(3),
+                Token::Less,
+                Token::I32(4),
+                Token::LessEqual,
+                Token::I32(5),
+                Token::Greater,
+                Token::I32(6),
+                Token::GreaterEqual,
+                Token::I32(7),
+                Token::Or,
+                Token::Ident("a".into()),
+                Token::Assign,
+                Token::I32(1),
+            ],
+            vec![
+                Token::If,
+                Token::Ident("a".into()),
+                Token::OpenBrace,
+                Token::I32(1),
+                Token::CloseBrace,
+                Token::Else,
+                Token::OpenBrace,
+                Token::I32(2),
+                Token::CloseBrace,
+            ],
+            vec![
+                Token::While,
+                Token::Ident("a".into()),
+                Token::OpenBrace,
+                Token::Ident("a".into()),
+                Token::Semicolon,
+                Token::CloseBrace,
+            ],
+            vec![
+                Token::For,
+                Token::Ident("i".into()),
+                Token::In,
+                Token::I32(0),
+                Token::DotDot,
+                Token::I32(10),
+                Token::OpenBrace,
+                Token::I32(1),
+                Token::DotDotEq,
+                Token::I32(2),
+                Token::CloseBrace,
+            ],
+            vec![Token::F64(1.5), Token::DotDot, Token::F64(2.0)],
+            vec![
+                Token::Fn,
+                Token::Ident("f".into()),
+                Token::OpenParen,
+                Token::Ident("a".into()),
+                Token::Comma,
+                Token::Ident("b".into()),
+                Token::CloseParen,
+                Token::OpenBrace,
+                Token::Return,
+                Token::Ident("a".into()),
+                Token::CloseBrace,
+            ],
+            vec![
+                Token::Pipe,
+                Token::Ident("x".into()),
+                Token::Comma,
+                Token::Ident("y".into()),
+                Token::Pipe,
+                Token::Ident("x".into()),
+                Token::Or,
+                Token::Ident("y".into()),
+            ],
+            vec![
+                Token::Const,
+                Token::Ident("half".into()),
+                Token::Assign,
+                Token::F64(0.5),
+            ],
         ];
 
         for idx in 0..tests.len() {
             let input = tests[idx];
             let expected = expectations[idx].clone();
 
-            let output = extract_token_stream(input.to_string()).unwrap();
+            let output: Vec<Token> = extract_token_stream(input.to_string())
+                .unwrap()
+                .into_iter()
+                .map(|spanned| spanned.token)
+                .collect();
             assert_eq!(output, expected);
         }
     }
+
+    #[test]
+    fn test_token_spans() {
+        let output = extract_token_stream("let ab = 10.5\n  + 1".to_string()).unwrap();
+        let spans: Vec<Span> = output.into_iter().map(|spanned| spanned.span).collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 3, 1, 1),
+                Span::new(4, 6, 1, 5),
+                Span::new(7, 8, 1, 8),
+                Span::new(9, 13, 1, 10),
+                Span::new(16, 17, 2, 3),
+                Span::new(18, 19, 2, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_newlines_separate_statements() {
+        let tokens = |source: &str| -> Vec<Token> {
+            extract_token_stream(source.to_string())
+                .unwrap()
+                .into_iter()
+                .map(|spanned| spanned.token)
+                .collect()
+        };
+
+        assert_eq!(
+            tokens("let a = 1\na\n"),
+            vec![
+                Token::Let,
+                Token::Ident("a".into()),
+                Token::Assign,
+                Token::I32(1),
+                Token::Semicolon,
+                Token::Ident("a".into()),
+            ]
+        );
+        // operators at either end of a line carry the statement on
+        assert_eq!(tokens("1 +\n2"), tokens("1 + 2"));
+        assert_eq!(tokens("1\n  * 2"), tokens("1 * 2"));
+        assert_eq!(
+            tokens("if a {\n1\n}\nelse {\n2\n}"),
+            tokens("if a { 1; } else { 2; }")
+        );
+        // and so do parentheses
+        assert_eq!(tokens("f(1,\n2\n)"), tokens("f(1, 2)"));
+        assert_eq!(tokens("f(|| {\n1\n})"), tokens("f(|| { 1; })"));
+        // `-` starts a new statement
+        assert_eq!(tokens("a\n-1"), tokens("a; -1"));
+        // tabs are whitespace too
+        assert_eq!(
+            tokens("fn f(n) {\n\tlet a = n\n\tif a > 1 {\n\t\ta\n\t}\n}\r\n\tf(2)\n"),
+            tokens("fn f(n) { let a = n; if a > 1 { a; }; }; f(2)")
+        );
+
+        let separator = extract_token_stream("ab\nc".to_string()).unwrap()[1].clone();
+        assert_eq!(separator.token, Token::Semicolon);
+        assert_eq!(separator.span, Span::new(2, 2, 1, 3));
+    }
+
+    #[test]
+    fn test_invalid_input_char() {
+        match extract_token_stream("1 + $".to_string()) {
+            Err(LexerError::InvalidInputChar(c, span)) => {
+                assert_eq!(c, "$");
+                assert_eq!(span, Span::new(4, 5, 1, 5));
+            }
+            other => panic!("expected invalid input char error, got {:?}", other),
+        }
+
+        // logical operators are always doubled
+        assert!(matches!(
+            extract_token_stream("true & false".to_string()),
+            Err(LexerError::InvalidInputChar(c, _)) if c == "&"
+        ));
+    }
 }
@@ -1,8 +1,13 @@
+use std::fmt;
+
+use crate::bigint::BigInt;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Ident(String),
-    F32(f32),
+    F64(f64),
     I32(i32),
+    BigInt(BigInt),
 
     Plus,
     Minus,
@@ -11,9 +16,144 @@ pub enum Token {
 
     OpenParen,
     CloseParen,
+    OpenBrace,
+    CloseBrace,
 
     Caret,
 
     Let,
+    Const,
     Assign,
+    If,
+    Else,
+    While,
+    For,
+    In,
+    Fn,
+    Return,
+    Semicolon,
+    Comma,
+    DotDot,
+    DotDotEq,
+
+    True,
+    False,
+
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+
+    And,
+    Or,
+    Bang,
+    Pipe,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(identifier) => write!(f, "{}", identifier),
+            Token::F64(value) => write!(f, "{:?}", value),
+            Token::I32(value) => write!(f, "{}", value),
+            Token::BigInt(value) => write!(f, "{}", value),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::OpenBrace => write!(f, "{{"),
+            Token::CloseBrace => write!(f, "}}"),
+            Token::Caret => write!(f, "^"),
+            Token::Let => write!(f, "let"),
+            Token::Const => write!(f, "const"),
+            Token::Assign => write!(f, "="),
+            Token::If => write!(f, "if"),
+            Token::Else => write!(f, "else"),
+            Token::While => write!(f, "while"),
+            Token::For => write!(f, "for"),
+            Token::In => write!(f, "in"),
+            Token::Fn => write!(f, "fn"),
+            Token::Return => write!(f, "return"),
+            Token::Semicolon => write!(f, ";"),
+            Token::Comma => write!(f, ","),
+            Token::DotDot => write!(f, ".."),
+            Token::DotDotEq => write!(f, "..="),
+            Token::True => write!(f, "true"),
+            Token::False => write!(f, "false"),
+            Token::Equal => write!(f, "=="),
+            Token::NotEqual => write!(f, "!="),
+            Token::Less => write!(f, "<"),
+            Token::LessEqual => write!(f, "<="),
+            Token::Greater => write!(f, ">"),
+            Token::GreaterEqual => write!(f, ">="),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::Bang => write!(f, "!"),
+            Token::Pipe => write!(f, "|"),
+        }
+    }
+}
+
+/// Location of a piece of the input: `start` and `end` are byte offsets
+/// (end exclusive) while `line` and `column` point to the first char,
+/// both starting at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    /// returns a span starting at `self` and finishing where `other` finishes
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+
+    /// returns an empty span right after `self`, which must be on a single line
+    pub fn after(self) -> Span {
+        let column = self.column + (self.end - self.start);
+        Span::new(self.end, self.end, self.line, column)
+    }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span::new(0, 0, 1, 1)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl SpannedToken {
+    pub fn new(token: Token, span: Span) -> SpannedToken {
+        SpannedToken { token, span }
+    }
 }
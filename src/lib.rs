@@ -0,0 +1,104 @@
+//! Alang is a small expression language with exact arithmetic, this crate
+//! has its lexer, parser and evaluator. `Interpreter` is the easiest way to
+//! run programs, while `tokenize`, `parse` and `evaluate` expose each step.
+
+mod ast;
+mod bigint;
+mod diagnostic;
+mod environment;
+mod eval;
+mod interpreter;
+mod lexer;
+mod parser;
+mod rational;
+mod stack;
+
+pub use diagnostic::{Diagnostic, ErrorKind};
+pub use environment::{AssignError, Environment};
+pub use eval::{DivisionMode, EvaluateError, EvaluationConfig, OverflowMode, Value};
+pub use interpreter::Interpreter;
+pub use lexer::LexerError;
+pub use parser::ParserError;
+
+// the types found in the ones above, so they can be named too
+pub use ast::{ASTNode, ASTNodeKind, NotAnOperatorError, Operator, Statement, StatementKind};
+pub use bigint::{BigInt, ParseBigIntError};
+pub use eval::{Builtin, Function, NativeFunction};
+pub use lexer::token::{Span, SpannedToken, Token};
+pub use rational::Rational;
+
+// the old name of `Value`, kept so code using it still builds
+#[doc(hidden)]
+#[allow(deprecated)]
+pub use eval::NumericObject;
+
+/// splits `source` into tokens
+pub fn tokenize(source: &str) -> Result<Vec<SpannedToken>, Diagnostic> {
+    Ok(lexer::extract_token_stream(source.to_string())?)
+}
+
+/// builds the statements of the program in `source`
+pub fn parse(source: &str) -> Result<Vec<Statement>, Diagnostic> {
+    Ok(parser::parse(tokenize(source)?)?)
+}
+
+/// runs the program in `source` against `environment`, producing
+/// the value of its last statement or `Value::Unit` when it has none,
+/// the `kind` of the diagnostic tells which step failed and why
+pub fn evaluate(
+    source: &str,
+    environment: &mut Environment,
+    config: &EvaluationConfig,
+) -> Result<Value, Diagnostic> {
+    let statements = parse(source)?;
+    Ok(eval::evaluate(&statements, environment, config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        evaluate, parse, tokenize, Environment, ErrorKind, EvaluateError, EvaluationConfig,
+        LexerError, Operator, ParserError, Span, SpannedToken, Statement, Token, Value,
+    };
+
+    #[test]
+    fn test_public_api() {
+        let tokens: Vec<SpannedToken> = tokenize("1 + 2").unwrap();
+        assert_eq!(tokens[1].token, Token::Plus);
+        assert_eq!(tokens[1].span, Span::new(2, 3, 1, 3));
+        let statements: Vec<Statement> = parse("let a = 1; a").unwrap();
+        assert_eq!(statements.len(), 2);
+
+        let mut environment = Environment::with_prelude();
+        let config = EvaluationConfig::default();
+        evaluate("let a = 20", &mut environment, &config).unwrap();
+        assert_eq!(
+            evaluate("a + 1", &mut environment, &config).unwrap(),
+            Value::I32(21)
+        );
+
+        let diagnostic = tokenize("1 $ 2").unwrap_err();
+        assert_eq!(diagnostic.code, "E0001");
+        assert!(matches!(
+            diagnostic.kind.as_deref(),
+            Some(ErrorKind::Lexer(LexerError::InvalidInputChar(..)))
+        ));
+
+        let diagnostic = parse("1 +").unwrap_err();
+        assert_eq!(diagnostic.code, "E0107");
+        assert!(matches!(
+            diagnostic.kind.as_deref(),
+            Some(ErrorKind::Parser(ParserError::MissingOperand(
+                Operator::Plus,
+                _
+            )))
+        ));
+
+        let diagnostic = evaluate("1 / 0", &mut environment, &config).unwrap_err();
+        assert_eq!(diagnostic.code, "E0204");
+        assert!(matches!(
+            diagnostic.kind.as_deref(),
+            Some(ErrorKind::Evaluate(EvaluateError::AttemptToDivideByZero(_)))
+        ));
+    }
+}
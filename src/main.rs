@@ -1,27 +1,290 @@
-mod ast;
-mod eval;
-mod lexer;
-mod parser;
-
-use eval::NumericObject;
+use alang::{DivisionMode, EvaluationConfig, Interpreter, OverflowMode, Value};
 use rustyline::{DefaultEditor, Result};
-use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+enum Mode {
+    Repl,
+    /// runs the file at the given path
+    Script(String),
+    /// evaluates the code given with `-e`
+    Expression(String),
+}
+
+struct Options {
+    config: EvaluationConfig,
+    mode: Mode,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> std::result::Result<Options, String> {
+    let mut config = EvaluationConfig::default();
+    let mut mode = Mode::Repl;
+
+    while let Some(arg) = args.next() {
+        if arg == "-e" && matches!(mode, Mode::Repl) {
+            match args.next() {
+                Some(source) => mode = Mode::Expression(source),
+                None => return Err("`-e` expects the code to evaluate".to_string()),
+            }
+        } else if arg == "--error-on-overflow" {
+            config.overflow = OverflowMode::Error;
+        } else if arg == "--allow-redeclaration" {
+            config.allow_redeclaration = true;
+        } else if let Some(mode) = arg.strip_prefix("--division=") {
+            config.division = mode.parse::<DivisionMode>()?;
+        } else if let Some(digits) = arg.strip_prefix("--precision=") {
+            match digits.parse::<usize>() {
+                Ok(digits) if digits > 0 => config.precision = Some(digits),
+                _ => {
+                    return Err(format!(
+                        "invalid precision `{}`, expected a positive number",
+                        digits
+                    ))
+                }
+            }
+        } else if !arg.starts_with('-') && matches!(mode, Mode::Repl) {
+            mode = Mode::Script(arg);
+        } else {
+            return Err(format!("unknown argument `{}`", arg));
+        }
+    }
+
+    Ok(Options { config, mode })
+}
+
+// statements without a value, like `let`, print nothing
+fn print_value(value: &Value, config: &EvaluationConfig) {
+    match (value, config.precision) {
+        (Value::Unit, _) => {}
+        (value, Some(digits)) => println!("{:.*}", digits, value),
+        (value, None) => println!("{}", value),
+    }
+}
+
+/// runs every statement of the file printing the value of the last one,
+/// errors are reported with the path of the file
+fn run_script(path: &str, config: EvaluationConfig) -> ExitCode {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: could not read `{}`: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut interpreter = Interpreter::new(config);
+    match interpreter.evaluate(&source) {
+        Ok(value) => {
+            print_value(&value, interpreter.config());
+            ExitCode::SUCCESS
+        }
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic.render_in_file(&source, path));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// evaluates `-e` code, errors go to stderr
+fn run_expression(source: &str, config: EvaluationConfig) -> ExitCode {
+    let mut interpreter = Interpreter::new(config);
+    match interpreter.evaluate(source) {
+        Ok(value) => {
+            print_value(&value, interpreter.config());
+            ExitCode::SUCCESS
+        }
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic.render(source));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// evaluates each line piped to stdin like the REPL would, without the
+/// banner and the prompt, failing if any of the lines did
+fn run_lines(input: impl BufRead, config: EvaluationConfig) -> ExitCode {
+    let mut interpreter = Interpreter::new(config);
+    let mut exit_code = ExitCode::SUCCESS;
+
+    for (number, line) in input.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("error: could not read stdin: {}", err);
+                return ExitCode::FAILURE;
+            }
+        };
 
-fn start_repl() -> Result<()> {
-    let mut evaluation_env: HashMap<String, NumericObject> = HashMap::new();
+        match interpreter.evaluate(&line) {
+            Ok(value) => print_value(&value, interpreter.config()),
+            Err(diagnostic) => {
+                let diagnostic = diagnostic.on_line(number + 1);
+                eprintln!("{}", diagnostic.render_in_file(&line, "<stdin>"));
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    exit_code
+}
+
+/// What a line starting with `:` asks the REPL to do
+enum Command<'a> {
+    /// shows the tokens of the code
+    Tokens(&'a str),
+    /// shows the syntax tree of the code
+    Ast(&'a str),
+    Env,
+    Reset,
+    Del(&'a str),
+    /// writes the variables to a file
+    Save(&'a str),
+    /// replaces the variables with the ones in a file
+    Load(&'a str),
+    Help,
+}
+
+const HELP: &str = "\
+:tokens <code>  show the tokens of the code
+:ast <code>     show the syntax tree of the code
+:env            list the global variables
+:reset          forget every variable
+:del <name>     forget the variable called `name`
+:save <file>    write the variables and functions to a file
+:load <file>    replace the variables with the ones saved in a file
+:help           show this message";
+
+fn parse_command(line: &str) -> std::result::Result<Command<'_>, String> {
+    let (name, argument) = match line.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (line, ""),
+    };
+
+    match (name, argument) {
+        (":tokens", code) => Ok(Command::Tokens(code)),
+        (":ast", code) => Ok(Command::Ast(code)),
+        (":env", "") => Ok(Command::Env),
+        (":reset", "") => Ok(Command::Reset),
+        (":help", "") => Ok(Command::Help),
+        (":del", variable) if !variable.is_empty() && !variable.contains(char::is_whitespace) => {
+            Ok(Command::Del(variable))
+        }
+        (":del", _) => Err("`:del` expects the name of a variable".to_string()),
+        (":save", "") | (":load", "") => Err(format!("`{}` expects the path of a file", name)),
+        (":save", path) => Ok(Command::Save(path)),
+        (":load", path) => Ok(Command::Load(path)),
+        (":env" | ":reset" | ":help", _) => Err(format!("`{}` takes no arguments", name)),
+        _ => Err(format!(
+            "unknown command `{}`, type `:help` to list them",
+            name
+        )),
+    }
+}
+
+fn run_command(interpreter: &mut Interpreter, command: Command) {
+    match command {
+        Command::Tokens(code) => match alang::tokenize(code) {
+            Ok(tokens) => {
+                for token in tokens {
+                    println!("{} {:?}", token.span, token.token);
+                }
+            }
+            Err(diagnostic) => println!("{}", diagnostic.render(code)),
+        },
+        Command::Ast(code) => match alang::parse(code) {
+            Ok(statements) => {
+                for statement in statements {
+                    println!("{}", statement.to_tree());
+                }
+            }
+            Err(diagnostic) => println!("{}", diagnostic.render(code)),
+        },
+        Command::Env => {
+            for (name, value, constant) in interpreter.environment().globals() {
+                let keyword = if constant { "const" } else { "let" };
+                println!("{} {} = {}", keyword, name, value);
+            }
+        }
+        Command::Reset => interpreter.reset(),
+        Command::Del(name) => {
+            if interpreter.remove(name) {
+                return;
+            }
+
+            if interpreter.environment().is_prelude(name) {
+                println!(
+                    "`{}` is a constant of the prelude and can't be removed",
+                    name
+                );
+            } else {
+                println!("`{}` is not declared", name);
+            }
+        }
+        Command::Save(path) => {
+            let (program, skipped) = interpreter.save();
+            if let Err(err) = fs::write(path, program) {
+                println!("error: could not write `{}`: {}", path, err);
+                return;
+            }
+
+            for name in skipped {
+                println!("warning: `{}` can't be saved and was left out", name);
+            }
+        }
+        Command::Load(path) => match fs::read_to_string(path) {
+            Ok(program) => {
+                if let Err(diagnostic) = interpreter.load(&program) {
+                    println!("{}", diagnostic.render_in_file(&program, path));
+                }
+            }
+            Err(err) => println!("error: could not read `{}`: {}", path, err),
+        },
+        Command::Help => println!("{}", HELP),
+    }
+}
+
+/// where the lines typed in the REPL are kept between sessions,
+/// `None` when the system has no data directory
+fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|data| data.join("alang").join("history"))
+}
+
+fn start_repl(config: EvaluationConfig) -> Result<()> {
+    let mut interpreter = Interpreter::new(config);
     println!(">> Alang REPL started, have fun!!");
 
     let mut rl = DefaultEditor::new()?;
+    let mut history = history_path();
+    if let Some(history) = &history {
+        // there is no history yet the first time the REPL runs
+        let _ = rl.load_history(history);
+
+        // a failure to create the directory shows up when saving
+        if let Some(directory) = history.parent() {
+            let _ = fs::create_dir_all(directory);
+        }
+    }
 
     loop {
         let readline = rl.readline(">> ");
+        if let Ok(line) = &readline {
+            if !line.trim().is_empty() {
+                rl.add_history_entry(line.as_str())?;
+                save_history(&mut rl, &mut history);
+            }
+        }
+
         match readline {
-            Ok(line) => match lexer::extract_token_stream(line) {
-                Ok(tokens) => {
-                    let exp_tree = parser::parse(tokens).unwrap();
-                    eval::evaluate(exp_tree, &mut evaluation_env);
-                }
-                Err(e) => println!("{:?}", e),
+            // commands are handled before the line reaches the lexer
+            Ok(line) if line.trim_start().starts_with(':') => match parse_command(line.trim()) {
+                Ok(command) => run_command(&mut interpreter, command),
+                Err(err) => println!("{}", err),
+            },
+            Ok(line) => match interpreter.evaluate(&line) {
+                Ok(value) => print_value(&value, interpreter.config()),
+                Err(diagnostic) => println!("{}", diagnostic.render(&line)),
             },
             Err(err) => {
                 println!("Error: {:?}", err);
@@ -33,6 +296,44 @@ fn start_repl() -> Result<()> {
     Ok(())
 }
 
-fn main() -> Result<()> {
-    start_repl()
+/// saves the history after every line, so it is not lost if the REPL
+/// is killed, it stops trying after the first failure
+fn save_history(rl: &mut DefaultEditor, history: &mut Option<PathBuf>) {
+    let Some(path) = history else {
+        return;
+    };
+
+    if let Err(err) = rl.save_history(path) {
+        eprintln!(
+            "error: could not save the history to `{}`: {}",
+            path.display(),
+            err
+        );
+        *history = None;
+    }
+}
+
+fn run(options: Options) -> ExitCode {
+    match options.mode {
+        Mode::Script(path) => run_script(&path, options.config),
+        Mode::Expression(source) => run_expression(&source, options.config),
+        Mode::Repl if !io::stdin().is_terminal() => run_lines(io::stdin().lock(), options.config),
+        Mode::Repl => match start_repl(options.config) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+fn main() -> ExitCode {
+    match parse_args(std::env::args().skip(1)) {
+        Ok(options) => run(options),
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(2)
+        }
+    }
 }
@@ -1,231 +1,850 @@
-use crate::ast::{ASTNode, Operator};
-use crate::lexer::token::Token;
+use crate::ast::{ASTNode, ASTNodeKind, NotAnOperatorError, Operator, Statement, StatementKind};
+use crate::diagnostic::{Diagnostic, ErrorKind};
+use crate::lexer::token::{Span, SpannedToken, Token};
+use crate::stack;
+use std::cmp::Ordering;
 use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParserError {
-    ExpectedClosingParent,
-    UnexpectedToken(Token),
-    TokenStreamNotEmpty,
+    ExpectedClosingParent(Span),
+    UnexpectedToken(Token, Span),
+    TokenStreamNotEmpty(Span),
     FailedToParseAllOperators,
-    ExpectedIdentifier,
+    ExpectedExpression(Span),
+    ExpectedIdentifier(Span),
+    ExpectedOperator(Token, Span),
+    MissingOperand(Operator, Span),
+    UnbalancedOperators,
+    ExpectedOpenBrace(Span),
+    ExpectedClosingBrace(Span),
+    ExpectedToken(Token, Span),
+    NestingTooDeep(Span),
 }
 
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParserError::ExpectedClosingParent => write!(f, "expected closing parent"),
-            ParserError::UnexpectedToken(t) => write!(f, "unexpected token: {:?}", t),
-            ParserError::TokenStreamNotEmpty => write!(f, "token stream not empty"),
+            ParserError::ExpectedClosingParent(span) => {
+                write!(f, "expected closing parent for the one opened at {}", span)
+            }
+            ParserError::UnexpectedToken(t, span) => {
+                write!(f, "unexpected token: {:?} at {}", t, span)
+            }
+            ParserError::TokenStreamNotEmpty(span) => {
+                write!(f, "token stream not empty at {}", span)
+            }
             ParserError::FailedToParseAllOperators => write!(f, "failed to parse all operators"),
-            ParserError::ExpectedIdentifier => write!(f, "expected variable identifier"),
+            ParserError::ExpectedExpression(span) => write!(f, "expected expression at {}", span),
+            ParserError::ExpectedIdentifier(span) => {
+                write!(f, "expected variable identifier at {}", span)
+            }
+            ParserError::ExpectedOperator(t, span) => {
+                write!(f, "expected operator, found: {:?} at {}", t, span)
+            }
+            ParserError::MissingOperand(op, span) => {
+                write!(f, "missing operand for `{}` at {}", op, span)
+            }
+            ParserError::UnbalancedOperators => write!(f, "unbalanced operators stack"),
+            ParserError::ExpectedOpenBrace(span) => write!(f, "expected open brace at {}", span),
+            ParserError::ExpectedClosingBrace(span) => {
+                write!(f, "expected closing brace for the one opened at {}", span)
+            }
+            ParserError::ExpectedToken(t, span) => write!(f, "expected `{}` at {}", t, span),
+            ParserError::NestingTooDeep(span) => write!(f, "nesting too deep at {}", span),
         }
     }
 }
 
-/**
+impl From<ParserError> for Diagnostic {
+    fn from(err: ParserError) -> Diagnostic {
+        let kind = ErrorKind::Parser(err.clone());
+        let diagnostic = match err {
+            ParserError::ExpectedClosingParent(span) => {
+                Diagnostic::new("E0101", "expected closing parent")
+                    .with_span(span)
+                    .with_note("note: this `(` is never closed")
+            }
+            ParserError::UnexpectedToken(t, span) => {
+                Diagnostic::new("E0102", format!("unexpected token `{}`", t))
+                    .with_span(span)
+                    .with_note("help: expected a number, a boolean, a variable, `(`, `-` or `!`")
+            }
+            ParserError::TokenStreamNotEmpty(span) => {
+                Diagnostic::new("E0103", "unexpected input after the expression").with_span(span)
+            }
+            ParserError::FailedToParseAllOperators => {
+                Diagnostic::new("E0104", "failed to parse all operators")
+            }
+            ParserError::ExpectedExpression(span) => {
+                Diagnostic::new("E0113", "expected an expression")
+                    .with_span(span)
+                    .with_note("note: the input finished before the expression")
+            }
+            ParserError::ExpectedIdentifier(span) => {
+                Diagnostic::new("E0105", "expected variable identifier")
+                    .with_span(span)
+                    .with_note(
+                        "help: variables are named like in `let name = 1` or `for name in 0..3`",
+                    )
+            }
+            ParserError::ExpectedOperator(t, span) => {
+                Diagnostic::new("E0106", format!("expected an operator, found `{}`", t))
+                    .with_span(span)
+                    .with_note("help: values must be combined with an operator like `+` or `*`")
+            }
+            ParserError::MissingOperand(op, span) => {
+                Diagnostic::new("E0107", format!("missing operand for `{}`", op)).with_span(span)
+            }
+            ParserError::UnbalancedOperators => {
+                Diagnostic::new("E0108", "unbalanced operators stack")
+                    .with_note("note: this is a bug in the parser")
+            }
+            ParserError::ExpectedOpenBrace(span) => Diagnostic::new("E0109", "expected `{`")
+                .with_span(span)
+                .with_note(
+                    "help: the bodies of `if`, `else`, `while`, `for` and `fn` go between `{` and `}`",
+                ),
+            ParserError::ExpectedClosingBrace(span) => {
+                Diagnostic::new("E0110", "expected closing brace")
+                    .with_span(span)
+                    .with_note("note: this `{` is never closed")
+            }
+            ParserError::ExpectedToken(t, span) => {
+                Diagnostic::new("E0111", format!("expected `{}`", t)).with_span(span)
+            }
+            ParserError::NestingTooDeep(span) => {
+                Diagnostic::new("E0112", "expression is nested too deeply")
+                    .with_span(span)
+                    .with_note(format!(
+                        "note: parentheses, blocks, closures and unary operators can be nested up to {} levels",
+                        MAX_NESTING_DEPTH
+                    ))
+            }
+        };
+
+        diagnostic.with_kind(kind)
+    }
+}
+
+/*
  * The grammar
  *
+ * L --> [S] {";" [S]}
+ * S --> E | ("let" | "const") v "=" E | "while" E K | "for" v "in" E K | "fn" v "(" [v {"," v}] ")" K | "return" [E]
  * E --> P {B P}
- * P --> v | "=" P | "(" E ")" | U P
- * B --> "+" | "-" | "*" | "/"
- * U --> "-"
+ * P --> v | "=" P | "(" E ")" | U P | I | K | P "(" [E {"," E}] ")" | C
+ * C --> "|" [v {"," v}] "|" E | "||" E
+ * I --> "if" E K ["else" (K | I)]
+ * K --> "{" L "}"
+ * B --> "+" | "-" | "*" | "/" | "^" | "=="  | "!=" | "<" | "<=" | ">" | ">=" | "&&" | "||"
+ *      | ".." | "..="
+ * U --> "-" | "!"
  *
  * where `v` is a terminal
  */
 
-pub fn parse(token_stream: Vec<Token>) -> Result<ASTNode, ParserError> {
+/// how deeply parentheses, blocks, unary operators, calls and the like can
+/// be nested, chains of binary operators like `1 + 2 + 3` don't count since
+/// they are not nested in the source, even if they are in the syntax tree
+const MAX_NESTING_DEPTH: usize = 1000;
+
+/// the depth of a construct nested in one at `depth`, `span` is where
+/// the error points to when it is too deep
+fn nested(depth: usize, span: Span) -> Result<usize, ParserError> {
+    match depth < MAX_NESTING_DEPTH {
+        true => Ok(depth + 1),
+        false => Err(ParserError::NestingTooDeep(span)),
+    }
+}
+
+pub fn parse(token_stream: Vec<SpannedToken>) -> Result<Vec<Statement>, ParserError> {
     let mut token_stream = token_stream.clone();
 
     // reverse the token stream so I can use pop to drain
     // tokens from the stream (like a stack)
     token_stream.reverse();
 
-    let mut operators_stack: Vec<Operator> = vec![Operator::Sentinel];
-    let mut operands_stack: Vec<ASTNode> = vec![];
-
-    parse_expression(&mut token_stream, &mut operators_stack, &mut operands_stack)?;
+    let statements = parse_statements(&mut token_stream, 0)?;
 
-    if !token_stream.is_empty() {
-        return Err(ParserError::TokenStreamNotEmpty);
+    if let Some(remaining) = token_stream.last() {
+        return Err(ParserError::TokenStreamNotEmpty(remaining.span));
     }
 
-    if operands_stack.len() != 1 {
-        return Err(ParserError::FailedToParseAllOperators);
+    Ok(statements)
+}
+
+/// parses `;` separated statements until the input or the enclosing block finishes
+fn parse_statements(
+    token_stream: &mut Vec<SpannedToken>,
+    depth: usize,
+) -> Result<Vec<Statement>, ParserError> {
+    let mut statements: Vec<Statement> = vec![];
+
+    loop {
+        match token_stream.last() {
+            None => break,
+            Some(tok) if tok.token == Token::CloseBrace => break,
+            // empty statements are allowed, e.g `a; ; b;`
+            Some(tok) if tok.token == Token::Semicolon => {
+                token_stream.pop();
+                continue;
+            }
+            Some(_) => {}
+        }
+
+        let statement = parse_statement(token_stream, depth)?;
+        let ends_with_block = matches!(
+            statement.kind,
+            StatementKind::While { .. }
+                | StatementKind::For { .. }
+                | StatementKind::Function { .. }
+        );
+        statements.push(statement);
+
+        // statements are separated by `;` unless they end with a block
+        match token_stream.last() {
+            Some(tok) if tok.token == Token::Semicolon || tok.token == Token::CloseBrace => {}
+            Some(tok) if !ends_with_block => {
+                return Err(ParserError::TokenStreamNotEmpty(tok.span))
+            }
+            _ => {}
+        }
     }
 
-    Ok(operands_stack.pop().unwrap())
+    Ok(statements)
 }
 
-fn parse_expression(
-    token_stream: &mut Vec<Token>,
-    operators_stack: &mut Vec<Operator>,
-    operands_stack: &mut Vec<ASTNode>,
-) -> Result<(), ParserError> {
-    parse_stmt(token_stream, operators_stack, operands_stack)?;
+fn parse_statement(
+    token_stream: &mut Vec<SpannedToken>,
+    depth: usize,
+) -> Result<Statement, ParserError> {
+    match token_stream.last() {
+        Some(tok) if tok.token == Token::While => {
+            // remove Token::While from the token stream
+            let while_tok = token_stream.pop().unwrap();
+
+            let condition = parse_standalone_expression(token_stream, while_tok.span, depth)?;
+            let body = parse_block(token_stream, while_tok.span, depth)?;
+            let span = while_tok.span.to(body.span);
+
+            Ok(Statement::new(
+                StatementKind::While { condition, body },
+                span,
+            ))
+        }
+        Some(tok) if tok.token == Token::Fn => parse_function(token_stream, depth),
+        Some(tok) if tok.token == Token::Let || tok.token == Token::Const => {
+            // remove Token::Let or Token::Const from the token stream
+            let let_tok = token_stream.pop().unwrap();
+            let constant = let_tok.token == Token::Const;
+
+            // the next token should be an identifier otherwise raise an error
+            let name = match token_stream.pop() {
+                Some(SpannedToken {
+                    token: Token::Ident(identifier),
+                    ..
+                }) => identifier,
+                Some(other) => return Err(ParserError::ExpectedIdentifier(other.span)),
+                None => return Err(ParserError::ExpectedIdentifier(let_tok.span)),
+            };
+
+            let assign_tok = match token_stream.pop() {
+                Some(assign_tok) if assign_tok.token == Token::Assign => assign_tok,
+                Some(other) => return Err(ParserError::ExpectedToken(Token::Assign, other.span)),
+                None => return Err(ParserError::ExpectedToken(Token::Assign, let_tok.span)),
+            };
+
+            let value = parse_standalone_expression(token_stream, assign_tok.span, depth)?;
+            let span = let_tok.span.to(value.span);
+
+            Ok(Statement::new(
+                StatementKind::Let {
+                    name,
+                    value,
+                    constant,
+                },
+                span,
+            ))
+        }
+        Some(tok) if tok.token == Token::Return => {
+            // remove Token::Return from the token stream
+            let return_tok = token_stream.pop().unwrap();
+
+            match token_stream.last() {
+                None => Ok(Statement::new(StatementKind::Return(None), return_tok.span)),
+                Some(tok) if tok.token == Token::Semicolon || tok.token == Token::CloseBrace => {
+                    Ok(Statement::new(StatementKind::Return(None), return_tok.span))
+                }
+                Some(_) => {
+                    let value = parse_standalone_expression(token_stream, return_tok.span, depth)?;
+                    let span = return_tok.span.to(value.span);
+
+                    Ok(Statement::new(StatementKind::Return(Some(value)), span))
+                }
+            }
+        }
+        Some(tok) if tok.token == Token::For => {
+            // remove Token::For from the token stream
+            let for_tok = token_stream.pop().unwrap();
+
+            let variable = match token_stream.pop() {
+                Some(SpannedToken {
+                    token: Token::Ident(identifier),
+                    ..
+                }) => identifier,
+                Some(other) => return Err(ParserError::ExpectedIdentifier(other.span)),
+                None => return Err(ParserError::ExpectedIdentifier(for_tok.span)),
+            };
+
+            let in_tok = match token_stream.pop() {
+                Some(in_tok) if in_tok.token == Token::In => in_tok,
+                Some(other) => return Err(ParserError::ExpectedToken(Token::In, other.span)),
+                None => return Err(ParserError::ExpectedToken(Token::In, for_tok.span)),
+            };
+
+            let iterable = parse_standalone_expression(token_stream, in_tok.span, depth)?;
+            let body = parse_block(token_stream, for_tok.span, depth)?;
+            let span = for_tok.span.to(body.span);
+
+            Ok(Statement::new(
+                StatementKind::For {
+                    variable,
+                    iterable,
+                    body,
+                },
+                span,
+            ))
+        }
+        // there is at least one token left, so the expression can't be missing
+        _ => {
+            let expression = parse_standalone_expression(token_stream, Span::default(), depth)?;
+            let span = expression.span;
+
+            Ok(Statement::new(StatementKind::Expression(expression), span))
+        }
+    }
+}
+
+/// parses the `,` separated arguments of a call until the closing
+/// parenthesis, returning them together with its span
+fn parse_arguments(
+    token_stream: &mut Vec<SpannedToken>,
+    open_span: Span,
+    depth: usize,
+) -> Result<(Vec<ASTNode>, Span), ParserError> {
+    let mut arguments: Vec<ASTNode> = vec![];
 
     loop {
-        if let Some(next_tok) = token_stream.last() {
-            match next_tok {
-                // when we reach a closing parent we just return since
-                // it is possible we are inside an open paren iteration
-                Token::CloseParen => break,
-                Token::Plus
-                | Token::Minus
-                | Token::Star
-                | Token::Slash
-                | Token::Caret
-                | Token::Assign => {
-                    let operator = match Operator::try_from(next_tok) {
-                        Ok(operator) => operator,
-                        _ => return Err(ParserError::UnexpectedToken(next_tok.clone())),
-                    };
-
-                    push_operator(operator, operators_stack, operands_stack);
-                    token_stream.pop();
+        match token_stream.last() {
+            Some(tok) if tok.token == Token::CloseParen => {
+                let close_tok = token_stream.pop().unwrap();
+                return Ok((arguments, close_tok.span));
+            }
+            None => return Err(ParserError::ExpectedClosingParent(open_span)),
+            Some(_) => {}
+        }
+
+        arguments.push(parse_standalone_expression(token_stream, open_span, depth)?);
 
-                    parse_stmt(token_stream, operators_stack, operands_stack)?;
+        match token_stream.last() {
+            Some(tok) if tok.token == Token::Comma => {
+                token_stream.pop();
+            }
+            Some(tok) if tok.token == Token::CloseParen => {}
+            Some(tok) => return Err(ParserError::ExpectedToken(Token::CloseParen, tok.span)),
+            None => return Err(ParserError::ExpectedClosingParent(open_span)),
+        }
+    }
+}
+
+/// parses `|a, b| body`, where `||` is a closure without parameters
+fn parse_closure(
+    token_stream: &mut Vec<SpannedToken>,
+    depth: usize,
+) -> Result<ASTNode, ParserError> {
+    let open_tok = token_stream.pop().unwrap();
+    let depth = nested(depth, open_tok.span)?;
+
+    let mut parameters: Vec<String> = vec![];
+    let mut close_span = open_tok.span;
+    if open_tok.token == Token::Pipe {
+        loop {
+            match token_stream.pop() {
+                Some(SpannedToken {
+                    token: Token::Pipe,
+                    span,
+                }) => {
+                    close_span = span;
+                    break;
                 }
-                _ => todo!("maybe an error?"),
+                Some(SpannedToken {
+                    token: Token::Ident(identifier),
+                    ..
+                }) => parameters.push(identifier),
+                Some(other) => return Err(ParserError::ExpectedIdentifier(other.span)),
+                None => return Err(ParserError::ExpectedToken(Token::Pipe, open_tok.span)),
+            }
+
+            match token_stream.last() {
+                Some(tok) if tok.token == Token::Comma => {
+                    token_stream.pop();
+                }
+                Some(tok) if tok.token == Token::Pipe => {}
+                Some(tok) => return Err(ParserError::ExpectedToken(Token::Pipe, tok.span)),
+                None => return Err(ParserError::ExpectedToken(Token::Pipe, open_tok.span)),
             }
-        } else {
-            break;
         }
     }
 
-    rewind_operands(operators_stack, operands_stack);
-    Ok(())
+    let body = parse_standalone_expression(token_stream, close_span, depth)?;
+    let span = open_tok.span.to(body.span);
+
+    Ok(ASTNode::new(
+        ASTNodeKind::Closure {
+            parameters,
+            body: Box::new(body),
+        },
+        span,
+    ))
 }
 
-fn rewind_operands(operators_stack: &mut Vec<Operator>, operands_stack: &mut Vec<ASTNode>) {
+/// parses `fn name(a, b) { ... }`
+fn parse_function(
+    token_stream: &mut Vec<SpannedToken>,
+    depth: usize,
+) -> Result<Statement, ParserError> {
+    // remove Token::Fn from the token stream
+    let fn_tok = token_stream.pop().unwrap();
+
+    let name = match token_stream.pop() {
+        Some(SpannedToken {
+            token: Token::Ident(identifier),
+            ..
+        }) => identifier,
+        Some(other) => return Err(ParserError::ExpectedIdentifier(other.span)),
+        None => return Err(ParserError::ExpectedIdentifier(fn_tok.span)),
+    };
+
+    let open_tok = match token_stream.pop() {
+        Some(open_tok) if open_tok.token == Token::OpenParen => open_tok,
+        Some(other) => return Err(ParserError::ExpectedToken(Token::OpenParen, other.span)),
+        None => return Err(ParserError::ExpectedToken(Token::OpenParen, fn_tok.span)),
+    };
+
+    let mut parameters: Vec<String> = vec![];
     loop {
-        let top_stack_operator = operators_stack.last().unwrap();
-        if *top_stack_operator == Operator::Sentinel {
-            break;
+        match token_stream.pop() {
+            Some(SpannedToken {
+                token: Token::CloseParen,
+                ..
+            }) => break,
+            Some(SpannedToken {
+                token: Token::Ident(identifier),
+                ..
+            }) => parameters.push(identifier),
+            Some(other) => return Err(ParserError::ExpectedIdentifier(other.span)),
+            None => return Err(ParserError::ExpectedClosingParent(open_tok.span)),
+        }
+
+        match token_stream.last() {
+            Some(tok) if tok.token == Token::Comma => {
+                token_stream.pop();
+            }
+            Some(tok) if tok.token == Token::CloseParen => {}
+            Some(tok) => return Err(ParserError::ExpectedToken(Token::CloseParen, tok.span)),
+            None => return Err(ParserError::ExpectedClosingParent(open_tok.span)),
         }
+    }
+
+    let body = parse_block(token_stream, fn_tok.span, depth)?;
+    let span = fn_tok.span.to(body.span);
+
+    Ok(Statement::new(
+        StatementKind::Function {
+            name,
+            parameters,
+            body,
+        },
+        span,
+    ))
+}
+
+/// parses an expression with its own operators and operands stacks, so
+/// it is not mixed with the expression it is nested in, `previous_span`
+/// is the token before it, the error points right after it when the
+/// input finishes before the expression
+fn parse_standalone_expression(
+    token_stream: &mut Vec<SpannedToken>,
+    previous_span: Span,
+    depth: usize,
+) -> Result<ASTNode, ParserError> {
+    let mut operators_stack: Vec<(Operator, Span)> = vec![(Operator::Sentinel, Span::default())];
+    let mut operands_stack: Vec<ASTNode> = vec![];
+
+    stack::grow(|| {
+        parse_expression(
+            token_stream,
+            &mut operators_stack,
+            &mut operands_stack,
+            depth,
+        )
+    })?;
 
-        pop_operator(operators_stack, operands_stack);
+    match (operands_stack.pop(), operands_stack.is_empty()) {
+        (Some(ast), true) => Ok(ast),
+        (None, _) => Err(ParserError::ExpectedExpression(previous_span.after())),
+        (Some(_), false) => Err(ParserError::FailedToParseAllOperators),
     }
 }
 
-fn parse_stmt(
-    token_stream: &mut Vec<Token>,
-    operators_stack: &mut Vec<Operator>,
+fn parse_expression(
+    token_stream: &mut Vec<SpannedToken>,
+    operators_stack: &mut Vec<(Operator, Span)>,
     operands_stack: &mut Vec<ASTNode>,
+    mut depth: usize,
 ) -> Result<(), ParserError> {
-    match token_stream.last() {
-        Some(current_tok) => match current_tok {
-            Token::Let => {
-                // remove Token::Let from the token stream
-                token_stream.pop().unwrap();
+    parse_stmt(token_stream, operators_stack, operands_stack, depth)?;
 
-                // the next token should be an identifier otherwise raise an error
-                match token_stream.pop().unwrap() {
-                    Token::Ident(identifier) => {
-                        operands_stack.push(ASTNode::Ident(identifier.clone()));
+    while let Some(next_tok) = token_stream.last() {
+        match next_tok.token {
+            // when we reach a closing parent, a brace or a comma we just return
+            // since it is possible we are inside an open paren iteration, the
+            // expression is the condition or the body of an `if` or a call argument
+            Token::CloseParen
+            | Token::OpenBrace
+            | Token::CloseBrace
+            | Token::Semicolon
+            | Token::Comma => break,
+            // a parenthesis right after an operand calls it
+            Token::OpenParen => {
+                let open_tok = token_stream.pop().unwrap();
+                let Some(callee) = operands_stack.pop() else {
+                    return Err(ParserError::UnexpectedToken(open_tok.token, open_tok.span));
+                };
+
+                depth = nested(depth, open_tok.span)?;
+                let (arguments, close_span) = parse_arguments(token_stream, open_tok.span, depth)?;
+                let span = callee.span.to(close_span);
+
+                operands_stack.push(ASTNode::new(
+                    ASTNodeKind::Call {
+                        callee: Box::new(callee),
+                        arguments,
+                    },
+                    span,
+                ));
+            }
+            Token::Plus
+            | Token::Minus
+            | Token::Star
+            | Token::Slash
+            | Token::Caret
+            | Token::Assign
+            | Token::Equal
+            | Token::NotEqual
+            | Token::Less
+            | Token::LessEqual
+            | Token::Greater
+            | Token::GreaterEqual
+            | Token::And
+            | Token::Or
+            | Token::DotDot
+            | Token::DotDotEq => {
+                let operator = match Operator::try_from(&next_tok.token) {
+                    Ok(operator) => operator,
+                    Err(NotAnOperatorError(token)) => {
+                        return Err(ParserError::UnexpectedToken(token, next_tok.span))
                     }
-                    _ => return Err(ParserError::ExpectedIdentifier),
-                }
-                Ok(())
+                };
+
+                push_operator((operator, next_tok.span), operators_stack, operands_stack)?;
+                token_stream.pop();
+
+                parse_stmt(token_stream, operators_stack, operands_stack, depth)?;
+            }
+            _ => {
+                return Err(ParserError::ExpectedOperator(
+                    next_tok.token.clone(),
+                    next_tok.span,
+                ))
             }
+        }
+    }
 
+    rewind_operands(operators_stack, operands_stack)
+}
+
+fn rewind_operands(
+    operators_stack: &mut Vec<(Operator, Span)>,
+    operands_stack: &mut Vec<ASTNode>,
+) -> Result<(), ParserError> {
+    loop {
+        match operators_stack.last() {
+            Some((Operator::Sentinel, _)) => break,
+            Some(_) => pop_operator(operators_stack, operands_stack)?,
+            None => return Err(ParserError::UnbalancedOperators),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_stmt(
+    token_stream: &mut Vec<SpannedToken>,
+    operators_stack: &mut Vec<(Operator, Span)>,
+    operands_stack: &mut Vec<ASTNode>,
+    depth: usize,
+) -> Result<(), ParserError> {
+    match token_stream.last() {
+        Some(current_tok) => match &current_tok.token {
             Token::Ident(identifier) => {
-                operands_stack.push(ASTNode::Ident(identifier.clone()));
+                operands_stack.push(ASTNode::new(
+                    ASTNodeKind::Ident(identifier.clone()),
+                    current_tok.span,
+                ));
                 token_stream.pop().unwrap();
 
                 Ok(())
             }
-            Token::F32(value) => {
-                operands_stack.push(ASTNode::F32(*value));
+            Token::F64(value) => {
+                operands_stack.push(ASTNode::new(ASTNodeKind::F64(*value), current_tok.span));
                 token_stream.pop().unwrap();
                 Ok(())
             }
             Token::I32(value) => {
-                operands_stack.push(ASTNode::I32(*value));
+                operands_stack.push(ASTNode::new(ASTNodeKind::I32(*value), current_tok.span));
+                token_stream.pop().unwrap();
+                Ok(())
+            }
+            Token::BigInt(value) => {
+                operands_stack.push(ASTNode::new(
+                    ASTNodeKind::BigInt(value.clone()),
+                    current_tok.span,
+                ));
+                token_stream.pop().unwrap();
+                Ok(())
+            }
+            Token::True | Token::False => {
+                let value = current_tok.token == Token::True;
+                operands_stack.push(ASTNode::new(ASTNodeKind::Bool(value), current_tok.span));
                 token_stream.pop().unwrap();
                 Ok(())
             }
             Token::OpenParen => {
-                token_stream.pop();
-                operators_stack.push(Operator::Sentinel);
-                parse_expression(token_stream, operators_stack, operands_stack)?;
+                let open_tok = token_stream.pop().unwrap();
+                operators_stack.push((Operator::Sentinel, open_tok.span));
+                let depth = nested(depth, open_tok.span)?;
+                stack::grow(|| {
+                    parse_expression(token_stream, operators_stack, operands_stack, depth)
+                })?;
 
                 // expect we end with a closing parenthesis
-                if let Some(end_token) = token_stream.last() {
-                    if *end_token != Token::CloseParen {
-                        return Err(ParserError::ExpectedClosingParent);
-                    }
+                match token_stream.last() {
+                    Some(end_tok) if end_tok.token == Token::CloseParen => {
+                        // the parenthesized expression spans from '(' to ')'
+                        if let Some(inner) = operands_stack.last_mut() {
+                            inner.span = open_tok.span.to(end_tok.span);
+                        }
 
-                    token_stream.pop();
-                    operators_stack.pop();
-                    return Ok(());
-                } else {
-                    return Err(ParserError::ExpectedClosingParent);
+                        token_stream.pop();
+                        operators_stack.pop();
+                        Ok(())
+                    }
+                    _ => Err(ParserError::ExpectedClosingParent(open_tok.span)),
                 }
             }
             Token::Minus => {
-                token_stream.pop();
-                operators_stack.push(Operator::Negative);
-                parse_stmt(token_stream, operators_stack, operands_stack)?;
+                let minus_tok = token_stream.pop().unwrap();
+                operators_stack.push((Operator::Negative, minus_tok.span));
+                let depth = nested(depth, minus_tok.span)?;
+                stack::grow(|| parse_stmt(token_stream, operators_stack, operands_stack, depth))?;
+                Ok(())
+            }
+            Token::OpenBrace => {
+                let block = parse_block(token_stream, current_tok.span, depth)?;
+                operands_stack.push(block);
                 Ok(())
             }
-            _ => Err(ParserError::UnexpectedToken(current_tok.clone())),
+            Token::Pipe | Token::Or => {
+                let closure = parse_closure(token_stream, depth)?;
+                operands_stack.push(closure);
+                Ok(())
+            }
+            Token::If => {
+                let if_expression = parse_if(token_stream, depth)?;
+                operands_stack.push(if_expression);
+                Ok(())
+            }
+            Token::Bang => {
+                let bang_tok = token_stream.pop().unwrap();
+                operators_stack.push((Operator::Not, bang_tok.span));
+                let depth = nested(depth, bang_tok.span)?;
+                stack::grow(|| parse_stmt(token_stream, operators_stack, operands_stack, depth))?;
+                Ok(())
+            }
+            _ => Err(ParserError::UnexpectedToken(
+                current_tok.token.clone(),
+                current_tok.span,
+            )),
         },
         None => Ok(()),
     }
 }
 
+fn parse_if(token_stream: &mut Vec<SpannedToken>, depth: usize) -> Result<ASTNode, ParserError> {
+    // remove Token::If from the token stream
+    let if_tok = token_stream.pop().unwrap();
+    let depth = nested(depth, if_tok.span)?;
+
+    let condition = parse_standalone_expression(token_stream, if_tok.span, depth)?;
+    let then_branch = parse_block(token_stream, if_tok.span, depth)?;
+    let mut span = if_tok.span.to(then_branch.span);
+
+    let else_branch = match token_stream.last() {
+        Some(SpannedToken {
+            token: Token::Else,
+            span: else_span,
+        }) => {
+            let else_span = *else_span;
+            token_stream.pop();
+
+            let else_branch = match token_stream.last() {
+                Some(SpannedToken {
+                    token: Token::If, ..
+                }) => stack::grow(|| parse_if(token_stream, depth))?,
+                _ => parse_block(token_stream, else_span, depth)?,
+            };
+
+            span = span.to(else_branch.span);
+            Some(Box::new(else_branch))
+        }
+        _ => None,
+    };
+
+    Ok(ASTNode::new(
+        ASTNodeKind::If {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch,
+        },
+        span,
+    ))
+}
+
+/// parses `{ L }`, `previous_span` is where the error points to
+/// when the input finishes before the opening brace
+fn parse_block(
+    token_stream: &mut Vec<SpannedToken>,
+    previous_span: Span,
+    depth: usize,
+) -> Result<ASTNode, ParserError> {
+    let open_tok = match token_stream.pop() {
+        Some(open_tok) if open_tok.token == Token::OpenBrace => open_tok,
+        Some(other) => return Err(ParserError::ExpectedOpenBrace(other.span)),
+        None => return Err(ParserError::ExpectedOpenBrace(previous_span)),
+    };
+
+    let depth = nested(depth, open_tok.span)?;
+    let statements = stack::grow(|| parse_statements(token_stream, depth))?;
+
+    match token_stream.pop() {
+        Some(end_tok) if end_tok.token == Token::CloseBrace => Ok(ASTNode::new(
+            ASTNodeKind::Block(statements),
+            open_tok.span.to(end_tok.span),
+        )),
+        _ => Err(ParserError::ExpectedClosingBrace(open_tok.span)),
+    }
+}
+
 fn push_operator(
-    op: Operator,
-    operators_stack: &mut Vec<Operator>,
+    op: (Operator, Span),
+    operators_stack: &mut Vec<(Operator, Span)>,
     operands_stack: &mut Vec<ASTNode>,
-) {
+) -> Result<(), ParserError> {
     loop {
-        // retrieve the top operator in the stack and check
-        // if it is greater than the given argument operator
-        if let Some(top_stack_operator) = operators_stack.last() {
-            if *top_stack_operator > op {
-                pop_operator(operators_stack, operands_stack)
-            } else {
-                break;
-            }
-        } else {
-            todo!("implement error here")
+        // retrieve the top operator in the stack and check if it binds
+        // tighter than the given argument operator, operators with the
+        // same precedence are popped too unless they group from the right
+        let Some((top_stack_operator, _)) = operators_stack.last() else {
+            return Err(ParserError::UnbalancedOperators);
+        };
+
+        let should_pop = match top_stack_operator.partial_cmp(&op.0) {
+            Some(Ordering::Greater) => true,
+            Some(Ordering::Equal) => !op.0.is_right_associative(),
+            _ => false,
+        };
+
+        if !should_pop {
+            break;
         }
+
+        pop_operator(operators_stack, operands_stack)?
     }
 
     operators_stack.push(op);
+    Ok(())
 }
 
-fn pop_operator(operators_stack: &mut Vec<Operator>, operands_stack: &mut Vec<ASTNode>) {
-    match operators_stack.last() {
-        Some(value) => match *value {
-            Operator::Plus
-            | Operator::Minus
-            | Operator::Multiplication
-            | Operator::Division
-            | Operator::Exponential
-            | Operator::Assign => {
-                let rhs = operands_stack.pop().unwrap();
-                let lhs = operands_stack.pop().unwrap();
-
-                operands_stack.push(ASTNode::BinaryExpr {
-                    op: operators_stack.pop().unwrap(),
+fn pop_operator(
+    operators_stack: &mut Vec<(Operator, Span)>,
+    operands_stack: &mut Vec<ASTNode>,
+) -> Result<(), ParserError> {
+    let Some((op, op_span)) = operators_stack.pop() else {
+        return Ok(());
+    };
+
+    match op {
+        Operator::Plus
+        | Operator::Minus
+        | Operator::Multiplication
+        | Operator::Division
+        | Operator::Exponential
+        | Operator::Assign
+        | Operator::Equal
+        | Operator::NotEqual
+        | Operator::Less
+        | Operator::LessEqual
+        | Operator::Greater
+        | Operator::GreaterEqual
+        | Operator::And
+        | Operator::Or
+        | Operator::Range
+        | Operator::RangeInclusive => {
+            let (Some(rhs), Some(lhs)) = (operands_stack.pop(), operands_stack.pop()) else {
+                return Err(ParserError::MissingOperand(op, op_span));
+            };
+            let span = lhs.span.to(rhs.span);
+
+            operands_stack.push(ASTNode::new(
+                ASTNodeKind::BinaryExpr {
+                    op,
                     lhs: Box::new(lhs),
                     rhs: Box::new(rhs),
-                })
-            }
-            Operator::Negative => {
-                let unary_expression = ASTNode::UnaryExpr {
-                    op: operators_stack.pop().unwrap(),
-                    inner: Box::new(operands_stack.pop().unwrap()),
-                };
+                },
+                span,
+            ));
+            Ok(())
+        }
+        Operator::Negative | Operator::Not => {
+            let Some(inner) = operands_stack.pop() else {
+                return Err(ParserError::MissingOperand(op, op_span));
+            };
+            let span = op_span.to(inner.span);
 
-                operands_stack.push(unary_expression);
-            }
-            _ => todo!("return an error if the popped operator is not supported"),
-        },
-        None => {}
+            let unary_expression = ASTNode::new(
+                ASTNodeKind::UnaryExpr {
+                    op,
+                    inner: Box::new(inner),
+                },
+                span,
+            );
+
+            operands_stack.push(unary_expression);
+            Ok(())
+        }
+        Operator::Sentinel => Err(ParserError::UnbalancedOperators),
     }
 }
 
@@ -233,13 +852,141 @@ fn pop_operator(operators_stack: &mut Vec<Operator>, operands_stack: &mut Vec<AS
 mod tests {
     #[allow(unused_imports)]
     use crate::{
-        ast::{ASTNode, Operator},
-        parser::parse,
+        ast::{ASTNode, ASTNodeKind, Operator, Statement, StatementKind},
+        lexer::{
+            extract_token_stream,
+            token::{Span, SpannedToken, Token},
+        },
+        parser::{parse, ParserError},
     };
 
+    fn spanned(tokens: Vec<Token>) -> Vec<SpannedToken> {
+        tokens
+            .into_iter()
+            .map(|token| SpannedToken::new(token, Span::default()))
+            .collect()
+    }
+
+    // builds the expected nodes without caring about spans
+    fn node(kind: ASTNodeKind) -> ASTNode {
+        ASTNode::new(kind, Span::default())
+    }
+
+    fn int(value: i32) -> ASTNode {
+        node(ASTNodeKind::I32(value))
+    }
+
+    fn boolean(value: bool) -> ASTNode {
+        node(ASTNodeKind::Bool(value))
+    }
+
+    fn unary(op: Operator, inner: ASTNode) -> ASTNode {
+        node(ASTNodeKind::UnaryExpr {
+            op,
+            inner: Box::new(inner),
+        })
+    }
+
+    fn binary(op: Operator, lhs: ASTNode, rhs: ASTNode) -> ASTNode {
+        node(ASTNodeKind::BinaryExpr {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        })
+    }
+
+    // parses input made of a single expression statement
+    fn single_expression(tokens: Vec<SpannedToken>) -> ASTNode {
+        let mut statements = parse(tokens).unwrap();
+        assert_eq!(statements.len(), 1);
+
+        match statements.pop().unwrap().kind {
+            StatementKind::Expression(expression) => expression,
+            kind => panic!("expected an expression, got {:?}", kind),
+        }
+    }
+
+    fn erase_statement_spans(statement: Statement) -> Statement {
+        let kind = match statement.kind {
+            StatementKind::Expression(expression) => {
+                StatementKind::Expression(erase_spans(expression))
+            }
+            StatementKind::While { condition, body } => StatementKind::While {
+                condition: erase_spans(condition),
+                body: erase_spans(body),
+            },
+            StatementKind::For {
+                variable,
+                iterable,
+                body,
+            } => StatementKind::For {
+                variable,
+                iterable: erase_spans(iterable),
+                body: erase_spans(body),
+            },
+            StatementKind::Function {
+                name,
+                parameters,
+                body,
+            } => StatementKind::Function {
+                name,
+                parameters,
+                body: erase_spans(body),
+            },
+            StatementKind::Let {
+                name,
+                value,
+                constant,
+            } => StatementKind::Let {
+                name,
+                value: erase_spans(value),
+                constant,
+            },
+            StatementKind::Return(value) => StatementKind::Return(value.map(erase_spans)),
+        };
+
+        Statement::new(kind, Span::default())
+    }
+
+    fn erase_spans(ast: ASTNode) -> ASTNode {
+        let kind = match ast.into_kind() {
+            ASTNodeKind::UnaryExpr { op, inner } => ASTNodeKind::UnaryExpr {
+                op,
+                inner: Box::new(erase_spans(*inner)),
+            },
+            ASTNodeKind::BinaryExpr { op, lhs, rhs } => ASTNodeKind::BinaryExpr {
+                op,
+                lhs: Box::new(erase_spans(*lhs)),
+                rhs: Box::new(erase_spans(*rhs)),
+            },
+            ASTNodeKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => ASTNodeKind::If {
+                condition: Box::new(erase_spans(*condition)),
+                then_branch: Box::new(erase_spans(*then_branch)),
+                else_branch: else_branch.map(|branch| Box::new(erase_spans(*branch))),
+            },
+            ASTNodeKind::Block(statements) => {
+                ASTNodeKind::Block(statements.into_iter().map(erase_statement_spans).collect())
+            }
+            ASTNodeKind::Call { callee, arguments } => ASTNodeKind::Call {
+                callee: Box::new(erase_spans(*callee)),
+                arguments: arguments.into_iter().map(erase_spans).collect(),
+            },
+            ASTNodeKind::Closure { parameters, body } => ASTNodeKind::Closure {
+                parameters,
+                body: Box::new(erase_spans(*body)),
+            },
+            kind => kind,
+        };
+
+        node(kind)
+    }
+
     #[test]
     fn test_parser() {
-        use crate::lexer::token::Token;
         let tokens_tests: Vec<Vec<Token>> = vec![
             vec![Token::I32(1), Token::Plus, Token::I32(1)],
             vec![
@@ -272,62 +1019,629 @@ mod tests {
         ];
 
         let expected_outputs: Vec<ASTNode> = vec![
-            ASTNode::BinaryExpr {
-                op: Operator::Plus,
-                lhs: Box::new(ASTNode::I32(1)),
-                rhs: Box::new(ASTNode::I32(1)),
-            },
-            ASTNode::BinaryExpr {
-                op: Operator::Plus,
-                lhs: Box::new(ASTNode::I32(1)),
-                rhs: Box::new(ASTNode::BinaryExpr {
-                    op: Operator::Multiplication,
-                    lhs: Box::new(ASTNode::I32(1)),
-                    rhs: Box::new(ASTNode::I32(2)),
-                }),
-            },
-            ASTNode::BinaryExpr {
-                op: Operator::Plus,
-                lhs: Box::new(ASTNode::UnaryExpr {
-                    op: Operator::Negative,
-                    inner: Box::new(ASTNode::I32(1)),
-                }),
-                rhs: Box::new(ASTNode::BinaryExpr {
-                    op: Operator::Division,
-                    lhs: Box::new(ASTNode::I32(1)),
-                    rhs: Box::new(ASTNode::I32(2)),
-                }),
-            },
-            ASTNode::BinaryExpr {
-                op: Operator::Minus,
-                lhs: Box::new(ASTNode::UnaryExpr {
-                    op: Operator::Negative,
-                    inner: Box::new(ASTNode::I32(1)),
-                }),
-                rhs: Box::new(ASTNode::I32(1)),
-            },
-            ASTNode::BinaryExpr {
-                op: Operator::Division,
-                lhs: Box::new(ASTNode::I32(10)),
-                rhs: Box::new(ASTNode::BinaryExpr {
-                    op: Operator::Plus,
-                    lhs: Box::new(ASTNode::I32(90)),
-                    rhs: Box::new(ASTNode::I32(8)),
-                }),
-            },
-            ASTNode::BinaryExpr {
-                op: Operator::Exponential,
-                lhs: Box::new(ASTNode::I32(2)),
-                rhs: Box::new(ASTNode::I32(2)),
-            },
+            binary(Operator::Plus, int(1), int(1)),
+            binary(
+                Operator::Plus,
+                int(1),
+                binary(Operator::Multiplication, int(1), int(2)),
+            ),
+            binary(
+                Operator::Plus,
+                unary(Operator::Negative, int(1)),
+                binary(Operator::Division, int(1), int(2)),
+            ),
+            binary(Operator::Minus, unary(Operator::Negative, int(1)), int(1)),
+            binary(
+                Operator::Division,
+                int(10),
+                binary(Operator::Plus, int(90), int(8)),
+            ),
+            binary(Operator::Exponential, int(2), int(2)),
         ];
 
         for idx in 0..tokens_tests.len() {
-            let tokens_to_test = tokens_tests[idx].clone();
+            let tokens_to_test = spanned(tokens_tests[idx].clone());
             let expected_ast = expected_outputs[idx].clone();
 
-            let output_ast = parse(tokens_to_test).unwrap();
-            assert_eq!(output_ast, expected_ast);
+            let output_ast = single_expression(tokens_to_test);
+            assert_eq!(erase_spans(output_ast), expected_ast);
         }
     }
+
+    fn parse_source(source: &str) -> ASTNode {
+        let tokens = extract_token_stream(source.to_string()).unwrap();
+        erase_spans(single_expression(tokens))
+    }
+
+    #[test]
+    fn test_associativity() {
+        assert_eq!(
+            parse_source("10 - 5 - 2"),
+            binary(
+                Operator::Minus,
+                binary(Operator::Minus, int(10), int(5)),
+                int(2)
+            )
+        );
+        assert_eq!(
+            parse_source("8 / 4 * 2"),
+            binary(
+                Operator::Multiplication,
+                binary(Operator::Division, int(8), int(4)),
+                int(2)
+            )
+        );
+        assert_eq!(
+            parse_source("2 ^ 3 ^ 2"),
+            binary(
+                Operator::Exponential,
+                int(2),
+                binary(Operator::Exponential, int(3), int(2))
+            )
+        );
+        assert_eq!(
+            parse_source("-2 ^ 2"),
+            unary(
+                Operator::Negative,
+                binary(Operator::Exponential, int(2), int(2))
+            )
+        );
+    }
+
+    #[test]
+    fn test_boolean_precedence() {
+        // arithmetic binds tighter than comparisons
+        assert_eq!(
+            parse_source("1 + 2 < 4"),
+            binary(
+                Operator::Less,
+                binary(Operator::Plus, int(1), int(2)),
+                int(4)
+            )
+        );
+        // comparisons bind tighter than equality
+        assert_eq!(
+            parse_source("1 < 2 == true"),
+            binary(
+                Operator::Equal,
+                binary(Operator::Less, int(1), int(2)),
+                boolean(true)
+            )
+        );
+        // `&&` binds tighter than `||`
+        assert_eq!(
+            parse_source("true || false && false"),
+            binary(
+                Operator::Or,
+                boolean(true),
+                binary(Operator::And, boolean(false), boolean(false))
+            )
+        );
+        assert_eq!(
+            parse_source("!true == false"),
+            binary(
+                Operator::Equal,
+                unary(Operator::Not, boolean(true)),
+                boolean(false)
+            )
+        );
+    }
+
+    fn if_node(condition: ASTNode, then_branch: ASTNode, else_branch: Option<ASTNode>) -> ASTNode {
+        node(ASTNodeKind::If {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: else_branch.map(Box::new),
+        })
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let block =
+            |expression: ASTNode| node(ASTNodeKind::Block(vec![expression_statement(expression)]));
+
+        assert_eq!(
+            parse_source("if 1 < 2 { 1 }"),
+            if_node(binary(Operator::Less, int(1), int(2)), block(int(1)), None)
+        );
+        assert_eq!(
+            parse_source("if true { 1 + 1 } else { 2 }"),
+            if_node(
+                boolean(true),
+                block(binary(Operator::Plus, int(1), int(1))),
+                Some(block(int(2)))
+            )
+        );
+        assert_eq!(
+            parse_source("if false { 1 } else if true { 2 } else { 3 }"),
+            if_node(
+                boolean(false),
+                block(int(1)),
+                Some(if_node(boolean(true), block(int(2)), Some(block(int(3)))))
+            )
+        );
+        // conditionals are expressions, so they can be operands
+        assert_eq!(
+            parse_source("1 + if true { 2 } else { 3 } * 4"),
+            binary(
+                Operator::Plus,
+                int(1),
+                binary(
+                    Operator::Multiplication,
+                    if_node(boolean(true), block(int(2)), Some(block(int(3)))),
+                    int(4)
+                )
+            )
+        );
+
+        let tokens = extract_token_stream("if a { 1 } else { 2 }".to_string()).unwrap();
+        assert_eq!(single_expression(tokens).span, Span::new(0, 21, 1, 1));
+    }
+
+    fn statements(source: &str) -> Vec<Statement> {
+        let tokens = extract_token_stream(source.to_string()).unwrap();
+        parse(tokens)
+            .unwrap()
+            .into_iter()
+            .map(erase_statement_spans)
+            .collect()
+    }
+
+    fn expression_statement(expression: ASTNode) -> Statement {
+        Statement::new(StatementKind::Expression(expression), Span::default())
+    }
+
+    #[test]
+    fn test_statements_and_blocks() {
+        let a = || node(ASTNodeKind::Ident("a".to_string()));
+
+        assert_eq!(statements(""), vec![]);
+        assert_eq!(
+            statements("1; 2;; 3;"),
+            vec![
+                expression_statement(int(1)),
+                expression_statement(int(2)),
+                expression_statement(int(3)),
+            ]
+        );
+        assert_eq!(
+            statements("{ a = 1; a }"),
+            vec![expression_statement(node(ASTNodeKind::Block(vec![
+                expression_statement(binary(Operator::Assign, a(), int(1))),
+                expression_statement(a()),
+            ])))]
+        );
+        assert_eq!(
+            statements("const a = 1; { let a = a }"),
+            vec![
+                Statement::new(
+                    StatementKind::Let {
+                        name: "a".to_string(),
+                        value: int(1),
+                        constant: true,
+                    },
+                    Span::default()
+                ),
+                expression_statement(node(ASTNodeKind::Block(vec![Statement::new(
+                    StatementKind::Let {
+                        name: "a".to_string(),
+                        value: a(),
+                        constant: false,
+                    },
+                    Span::default()
+                )]))),
+            ]
+        );
+        assert_eq!(
+            statements("1 + {}"),
+            vec![expression_statement(binary(
+                Operator::Plus,
+                int(1),
+                node(ASTNodeKind::Block(vec![]))
+            ))]
+        );
+        // a loop ends with its body so it needs no `;` after it
+        assert_eq!(
+            statements("while a < 3 { a = a + 1 } a"),
+            vec![
+                Statement::new(
+                    StatementKind::While {
+                        condition: binary(Operator::Less, a(), int(3)),
+                        body: node(ASTNodeKind::Block(vec![expression_statement(binary(
+                            Operator::Assign,
+                            a(),
+                            binary(Operator::Plus, a(), int(1))
+                        ))])),
+                    },
+                    Span::default()
+                ),
+                expression_statement(a()),
+            ]
+        );
+
+        let tokens = extract_token_stream("while a { a }; 1".to_string()).unwrap();
+        let spans: Vec<Span> = parse(tokens)
+            .unwrap()
+            .into_iter()
+            .map(|statement| statement.span)
+            .collect();
+        assert_eq!(
+            spans,
+            vec![Span::new(0, 13, 1, 1), Span::new(15, 16, 1, 16)]
+        );
+    }
+
+    #[test]
+    fn test_for_loops_and_ranges() {
+        let i = || node(ASTNodeKind::Ident("i".to_string()));
+
+        // ranges bind looser than arithmetic and comparisons
+        assert_eq!(
+            parse_source("0..n + 1"),
+            binary(
+                Operator::Range,
+                int(0),
+                binary(
+                    Operator::Plus,
+                    node(ASTNodeKind::Ident("n".to_string())),
+                    int(1)
+                )
+            )
+        );
+        assert_eq!(
+            parse_source("1..=2"),
+            binary(Operator::RangeInclusive, int(1), int(2))
+        );
+        assert_eq!(
+            statements("for i in 0..3 { i } i"),
+            vec![
+                Statement::new(
+                    StatementKind::For {
+                        variable: "i".to_string(),
+                        iterable: binary(Operator::Range, int(0), int(3)),
+                        body: node(ASTNodeKind::Block(vec![expression_statement(i())])),
+                    },
+                    Span::default()
+                ),
+                expression_statement(i()),
+            ]
+        );
+
+        assert!(matches!(
+            parse_err("for 1 in 0..3 {}"),
+            ParserError::ExpectedIdentifier(span) if span == Span::new(4, 5, 1, 5)
+        ));
+        assert!(matches!(
+            parse_err("for i 0..3 {}"),
+            ParserError::ExpectedToken(Token::In, span) if span == Span::new(6, 7, 1, 7)
+        ));
+        assert!(matches!(
+            parse_err("for i"),
+            ParserError::ExpectedToken(Token::In, span) if span == Span::new(0, 3, 1, 1)
+        ));
+        assert!(matches!(
+            parse_err("for i in 0..3"),
+            ParserError::ExpectedOpenBrace(_)
+        ));
+    }
+
+    #[test]
+    fn test_functions_and_calls() {
+        let ident = |name: &str| node(ASTNodeKind::Ident(name.to_string()));
+        let call = |callee: ASTNode, arguments: Vec<ASTNode>| {
+            node(ASTNodeKind::Call {
+                callee: Box::new(callee),
+                arguments,
+            })
+        };
+
+        assert_eq!(parse_source("f()"), call(ident("f"), vec![]));
+        // calls bind tighter than any operator
+        assert_eq!(
+            parse_source("-f(1, 2 + 3) ^ 2"),
+            unary(
+                Operator::Negative,
+                binary(
+                    Operator::Exponential,
+                    call(
+                        ident("f"),
+                        vec![int(1), binary(Operator::Plus, int(2), int(3))]
+                    ),
+                    int(2)
+                )
+            )
+        );
+        assert_eq!(
+            parse_source("f(1)(2)"),
+            call(call(ident("f"), vec![int(1)]), vec![int(2)])
+        );
+        assert_eq!(
+            statements("fn add(a, b) { return a + b; } add(1, 2)"),
+            vec![
+                Statement::new(
+                    StatementKind::Function {
+                        name: "add".to_string(),
+                        parameters: vec!["a".to_string(), "b".to_string()],
+                        body: node(ASTNodeKind::Block(vec![Statement::new(
+                            StatementKind::Return(Some(binary(
+                                Operator::Plus,
+                                ident("a"),
+                                ident("b")
+                            ))),
+                            Span::default()
+                        )])),
+                    },
+                    Span::default()
+                ),
+                expression_statement(call(ident("add"), vec![int(1), int(2)])),
+            ]
+        );
+        assert_eq!(
+            statements("fn f() { return }"),
+            vec![Statement::new(
+                StatementKind::Function {
+                    name: "f".to_string(),
+                    parameters: vec![],
+                    body: node(ASTNodeKind::Block(vec![Statement::new(
+                        StatementKind::Return(None),
+                        Span::default()
+                    )])),
+                },
+                Span::default()
+            )]
+        );
+
+        assert!(matches!(
+            parse_err("f(1, 2"),
+            ParserError::ExpectedClosingParent(span) if span == Span::new(1, 2, 1, 2)
+        ));
+        assert!(matches!(
+            parse_err("f(1 2)"),
+            ParserError::ExpectedOperator(Token::I32(2), _)
+        ));
+        assert!(matches!(
+            parse_err("f(1; 2)"),
+            ParserError::ExpectedToken(Token::CloseParen, span) if span == Span::new(3, 4, 1, 4)
+        ));
+        assert!(matches!(
+            parse_err("fn (a) {}"),
+            ParserError::ExpectedIdentifier(_)
+        ));
+        assert!(matches!(
+            parse_err("fn f a {}"),
+            ParserError::ExpectedToken(Token::OpenParen, _)
+        ));
+        assert!(matches!(
+            parse_err("fn f(a b) {}"),
+            ParserError::ExpectedToken(Token::CloseParen, _)
+        ));
+        assert!(matches!(
+            parse_err("fn f(1) {}"),
+            ParserError::ExpectedIdentifier(_)
+        ));
+        assert!(matches!(
+            parse_err("fn f(a)"),
+            ParserError::ExpectedOpenBrace(_)
+        ));
+    }
+
+    #[test]
+    fn test_closures() {
+        let ident = |name: &str| node(ASTNodeKind::Ident(name.to_string()));
+        let closure = |parameters: Vec<&str>, body: ASTNode| {
+            node(ASTNodeKind::Closure {
+                parameters: parameters.into_iter().map(String::from).collect(),
+                body: Box::new(body),
+            })
+        };
+
+        // the body takes the rest of the expression
+        assert_eq!(
+            parse_source("add = |x| x + n"),
+            binary(
+                Operator::Assign,
+                ident("add"),
+                closure(vec!["x"], binary(Operator::Plus, ident("x"), ident("n")))
+            )
+        );
+        assert_eq!(parse_source("|| 1"), closure(vec![], int(1)));
+        assert_eq!(
+            parse_source("f(|a, b| a, 2)"),
+            node(ASTNodeKind::Call {
+                callee: Box::new(ident("f")),
+                arguments: vec![closure(vec!["a", "b"], ident("a")), int(2)],
+            })
+        );
+        // `||` between operands is still the logical or
+        assert_eq!(
+            parse_source("a || b"),
+            binary(Operator::Or, ident("a"), ident("b"))
+        );
+
+        assert!(matches!(
+            parse_err("|x x + 1"),
+            ParserError::ExpectedToken(Token::Pipe, span) if span == Span::new(3, 4, 1, 4)
+        ));
+        assert!(matches!(
+            parse_err("|x"),
+            ParserError::ExpectedToken(Token::Pipe, span) if span == Span::new(0, 1, 1, 1)
+        ));
+        assert!(matches!(
+            parse_err("|1| 1"),
+            ParserError::ExpectedIdentifier(_)
+        ));
+        assert!(matches!(
+            parse_err("|x|"),
+            ParserError::ExpectedExpression(span) if span == Span::new(3, 3, 1, 4)
+        ));
+    }
+
+    #[test]
+    fn test_parser_spans() {
+        let tokens = extract_token_stream("-a + (1 * 2)".to_string()).unwrap();
+        let output_ast = single_expression(tokens);
+
+        assert_eq!(output_ast.span, Span::new(0, 12, 1, 1));
+        match output_ast.into_kind() {
+            ASTNodeKind::BinaryExpr { lhs, rhs, .. } => {
+                assert_eq!(lhs.span, Span::new(0, 2, 1, 1));
+                assert_eq!(rhs.span, Span::new(5, 12, 1, 6));
+            }
+            kind => panic!("expected binary expression, got {:?}", kind),
+        }
+    }
+
+    fn parse_err(source: &str) -> ParserError {
+        let tokens = extract_token_stream(source.to_string()).unwrap();
+        parse(tokens).unwrap_err()
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let within_limit = [
+            format!("{}1{}", "(".repeat(900), ")".repeat(900)),
+            format!("{}1", "-".repeat(900)),
+            format!("{}1{}", "{".repeat(900), "}".repeat(900)),
+            // operators one after the other are not nested
+            format!("1{}", " + 1".repeat(100_000)),
+            format!("1{}", " * 2 - 1".repeat(100_000)),
+        ];
+        for source in within_limit {
+            let tokens = extract_token_stream(source).unwrap();
+            assert!(parse(tokens).is_ok());
+        }
+
+        let too_deep = [
+            format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000)),
+            format!("{}1", "-".repeat(100_000)),
+            format!("{}1", "!".repeat(100_000)),
+            format!("{}1{}", "{".repeat(100_000), "}".repeat(100_000)),
+            format!("{}1", "|x| ".repeat(100_000)),
+            format!("{}1", "if true { 1 } else ".repeat(100_000)),
+            format!("f{}", "()".repeat(100_000)),
+        ];
+        for source in too_deep {
+            assert!(matches!(parse_err(&source), ParserError::NestingTooDeep(_)));
+        }
+
+        // the error points to where the limit is reached
+        let source = format!("{}1{}", "(".repeat(1001), ")".repeat(1001));
+        assert!(matches!(
+            parse_err(&source),
+            ParserError::NestingTooDeep(span) if span == Span::new(1000, 1001, 1, 1001)
+        ));
+    }
+
+    #[test]
+    fn test_incomplete_input() {
+        let tests: Vec<(&'static str, Span)> = vec![
+            ("if", Span::new(2, 2, 1, 3)),
+            ("let a =", Span::new(7, 7, 1, 8)),
+            ("const a =", Span::new(9, 9, 1, 10)),
+            ("while", Span::new(5, 5, 1, 6)),
+            ("for i in", Span::new(8, 8, 1, 9)),
+            ("|x|", Span::new(3, 3, 1, 4)),
+            ("||", Span::new(2, 2, 1, 3)),
+            ("1;\nlet a =", Span::new(10, 10, 2, 8)),
+        ];
+
+        for (source, expected) in tests {
+            match parse_err(source) {
+                ParserError::ExpectedExpression(span) => assert_eq!(span, expected, "{}", source),
+                err => panic!(
+                    "expected a missing expression in {:?}, got {:?}",
+                    source, err
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parser_errors() {
+        assert!(matches!(
+            parse_err("1 2"),
+            ParserError::ExpectedOperator(Token::I32(2), span) if span == Span::new(2, 3, 1, 3)
+        ));
+        assert!(matches!(
+            parse_err("let"),
+            ParserError::ExpectedIdentifier(span) if span == Span::new(0, 3, 1, 1)
+        ));
+        assert!(matches!(
+            parse_err("let 1 = 2"),
+            ParserError::ExpectedIdentifier(span) if span == Span::new(4, 5, 1, 5)
+        ));
+        assert!(matches!(
+            parse_err("const"),
+            ParserError::ExpectedIdentifier(span) if span == Span::new(0, 5, 1, 1)
+        ));
+        assert!(matches!(
+            parse_err("let a 1"),
+            ParserError::ExpectedToken(Token::Assign, span) if span == Span::new(6, 7, 1, 7)
+        ));
+        assert!(matches!(
+            parse_err("1 + let a = 1"),
+            ParserError::UnexpectedToken(Token::Let, _)
+        ));
+        assert!(matches!(
+            parse_err("1 +"),
+            ParserError::MissingOperand(Operator::Plus, span) if span == Span::new(2, 3, 1, 3)
+        ));
+        assert!(matches!(
+            parse_err("-"),
+            ParserError::MissingOperand(Operator::Negative, _)
+        ));
+        assert!(matches!(
+            parse_err("1 + * 2"),
+            ParserError::UnexpectedToken(Token::Star, _)
+        ));
+        assert!(matches!(
+            parse_err("(1 + 2"),
+            ParserError::ExpectedClosingParent(_)
+        ));
+        assert!(matches!(
+            parse_err("1 + 2)"),
+            ParserError::TokenStreamNotEmpty(_)
+        ));
+        assert!(matches!(
+            parse_err("if true }"),
+            ParserError::ExpectedOpenBrace(span) if span == Span::new(8, 9, 1, 9)
+        ));
+        assert!(matches!(
+            parse_err("if true"),
+            ParserError::ExpectedOpenBrace(span) if span == Span::new(0, 2, 1, 1)
+        ));
+        assert!(matches!(
+            parse_err("if true { 1"),
+            ParserError::ExpectedClosingBrace(span) if span == Span::new(8, 9, 1, 9)
+        ));
+        assert!(matches!(
+            parse_err("if true { 1 } else 2"),
+            ParserError::ExpectedOpenBrace(_)
+        ));
+        // `{ 1 }` is taken as the condition, so the body is missing
+        assert!(matches!(
+            parse_err("if { 1 }"),
+            ParserError::ExpectedOpenBrace(span) if span == Span::new(0, 2, 1, 1)
+        ));
+        assert!(matches!(
+            parse_err("1 }"),
+            ParserError::TokenStreamNotEmpty(_)
+        ));
+        assert!(matches!(
+            parse_err("{ 1; 2"),
+            ParserError::ExpectedClosingBrace(span) if span == Span::new(0, 1, 1, 1)
+        ));
+        assert!(matches!(
+            parse_err("while true"),
+            ParserError::ExpectedOpenBrace(_)
+        ));
+        assert!(matches!(
+            parse_err("1 + while true { 1 }"),
+            ParserError::UnexpectedToken(Token::While, _)
+        ));
+        assert!(matches!(
+            parse_err("if true { 1 } 2"),
+            ParserError::ExpectedOperator(Token::I32(2), span) if span == Span::new(14, 15, 1, 15)
+        ));
+    }
 }
@@ -0,0 +1,238 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::bigint::BigInt;
+
+/// Exact fraction kept normalized: the denominator is always positive
+/// and shares no common factor with the numerator
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: BigInt,
+    denominator: BigInt,
+}
+
+fn gcd(lhs: &BigInt, rhs: &BigInt) -> BigInt {
+    let (mut a, mut b) = (lhs.abs(), rhs.abs());
+    while let Some(remainder) = a.checked_rem(&b) {
+        a = b;
+        b = remainder;
+    }
+
+    a
+}
+
+impl Rational {
+    /// `None` when the denominator is zero
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Option<Rational> {
+        if denominator.is_zero() {
+            return None;
+        }
+
+        Some(Rational::reduce(numerator, denominator))
+    }
+
+    // expects a non zero denominator, so the gcd is never zero either
+    fn reduce(numerator: BigInt, denominator: BigInt) -> Rational {
+        let divisor = gcd(&numerator, &denominator);
+        let (mut numerator, mut denominator) = (&numerator / &divisor, &denominator / &divisor);
+
+        if denominator.is_negative() {
+            numerator = -numerator;
+            denominator = -denominator;
+        }
+
+        Rational {
+            numerator,
+            denominator,
+        }
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == BigInt::from(1)
+    }
+
+    /// `None` when dividing by zero
+    pub fn checked_div(&self, other: &Rational) -> Option<Rational> {
+        Rational::new(
+            &self.numerator * &other.denominator,
+            &self.denominator * &other.numerator,
+        )
+    }
+
+    pub fn recip(&self) -> Option<Rational> {
+        Rational::new(self.denominator.clone(), self.numerator.clone())
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator.to_f64() / self.denominator.to_f64()
+    }
+
+    /// largest integer that is not greater than the fraction
+    pub fn floor(&self) -> BigInt {
+        // the division truncates towards zero, which is one too
+        // much for negative fractions
+        let quotient = &self.numerator / &self.denominator;
+        match self.numerator.is_negative() && !self.is_integer() {
+            true => quotient - BigInt::from(1),
+            false => quotient,
+        }
+    }
+
+    /// smallest integer that is not less than the fraction
+    pub fn ceil(&self) -> BigInt {
+        -(-self.clone()).floor()
+    }
+
+    /// closest integer, halfway cases are rounded away from zero
+    pub fn round(&self) -> BigInt {
+        let half = Rational::reduce(BigInt::from(1), BigInt::from(2));
+        match self.numerator.is_negative() {
+            true => -(-self.clone() + half).floor(),
+            false => (self.clone() + half).floor(),
+        }
+    }
+}
+
+impl From<BigInt> for Rational {
+    fn from(value: BigInt) -> Rational {
+        Rational {
+            numerator: value,
+            denominator: BigInt::from(1),
+        }
+    }
+}
+
+impl From<i32> for Rational {
+    fn from(value: i32) -> Rational {
+        Rational::from(BigInt::from(value))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.is_integer() {
+            true => write!(f, "{}", self.numerator),
+            false => write!(f, "{}/{}", self.numerator, self.denominator),
+        }
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        // denominators are positive so cross multiplying keeps the ordering
+        (&self.numerator * &other.denominator).cmp(&(&other.numerator * &self.denominator))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        Rational {
+            numerator: -self.numerator,
+            denominator: self.denominator,
+        }
+    }
+}
+
+impl Add for Rational {
+    type Output = Rational;
+
+    fn add(self, other: Rational) -> Rational {
+        Rational::reduce(
+            &(&self.numerator * &other.denominator) + &(&other.numerator * &self.denominator),
+            &self.denominator * &other.denominator,
+        )
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+
+    fn sub(self, other: Rational) -> Rational {
+        self + -other
+    }
+}
+
+impl Mul for Rational {
+    type Output = Rational;
+
+    fn mul(self, other: Rational) -> Rational {
+        Rational::reduce(
+            &self.numerator * &other.numerator,
+            &self.denominator * &other.denominator,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rational;
+    use crate::bigint::BigInt;
+
+    fn ratio(numerator: i32, denominator: i32) -> Rational {
+        Rational::new(BigInt::from(numerator), BigInt::from(denominator)).unwrap()
+    }
+
+    #[test]
+    fn test_normalization() {
+        assert_eq!(ratio(14, 4).to_string(), "7/2");
+        assert_eq!(ratio(3, -6).to_string(), "-1/2");
+        assert_eq!(ratio(-3, -6).to_string(), "1/2");
+        assert_eq!(ratio(0, -5).to_string(), "0");
+        assert_eq!(ratio(10, 5).to_string(), "2");
+        assert!(ratio(10, 5).is_integer());
+        assert_eq!(Rational::new(BigInt::from(1), BigInt::zero()), None);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(ratio(1, 2) + ratio(1, 3), ratio(5, 6));
+        assert_eq!(ratio(1, 2) - ratio(3, 4), ratio(-1, 4));
+        assert_eq!(ratio(2, 3) * ratio(9, 4), ratio(3, 2));
+        assert_eq!(ratio(2, 3).checked_div(&ratio(4, 9)), Some(ratio(3, 2)));
+        assert_eq!(ratio(2, 3).checked_div(&ratio(0, 1)), None);
+        assert_eq!(ratio(-2, 3).recip(), Some(ratio(-3, 2)));
+        assert_eq!(ratio(7, 2).to_f64(), 3.5);
+    }
+
+    #[test]
+    fn test_rounding() {
+        let tests: Vec<(Rational, i32, i32, i32)> = vec![
+            (ratio(7, 2), 3, 4, 4),
+            (ratio(-7, 2), -4, -3, -4),
+            (ratio(10, 3), 3, 4, 3),
+            (ratio(-10, 3), -4, -3, -3),
+            (ratio(4, 1), 4, 4, 4),
+            (ratio(-1, 3), -1, 0, 0),
+        ];
+
+        for (value, floor, ceil, round) in tests {
+            assert_eq!(value.floor(), BigInt::from(floor), "floor of {}", value);
+            assert_eq!(value.ceil(), BigInt::from(ceil), "ceil of {}", value);
+            assert_eq!(value.round(), BigInt::from(round), "round of {}", value);
+        }
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(ratio(1, 3) < ratio(1, 2));
+        assert!(ratio(-1, 2) < ratio(-1, 3));
+        assert_eq!(ratio(2, 4), ratio(1, 2));
+    }
+}
@@ -0,0 +1,13 @@
+/// bytes of stack that must be left when entering a nested expression,
+/// enough for the frames used until the next check in debug builds
+const RED_ZONE: usize = 256 * 1024;
+
+/// size of each new piece of stack
+const STACK_GROWTH: usize = 4 * 1024 * 1024;
+
+/// runs `f` on a new piece of stack, allocated on the heap, when the current
+/// one is running out, so the recursion of the parser and the evaluator never
+/// overflows whatever thread they run on, the nesting limits keep it bounded
+pub(crate) fn grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, STACK_GROWTH, f)
+}